@@ -0,0 +1,95 @@
+//! Times program matching over every `tests/fixtures/*.binpb` block: each instruction's
+//! program id encoded to base58 and looked up as a string, as the handlers used to, against
+//! the raw 32-byte keys they compare now, then the handlers that match programs, cloning
+//! their input block included.
+//!
+//! `cargo bench --bench program_matching`
+
+use std::collections::HashSet;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use jupiter_dex_substreams::constants::{DCA_PROGRAM_ID, JUPITER_PROGRAM_IDS, LIMIT_ORDER_PROGRAM_ID};
+use jupiter_dex_substreams::*;
+use prost::Message;
+use substreams_solana::base58;
+use substreams_solana::pb::sf::solana::r#type::v1::Block;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const RUNS: u32 = 10_000;
+
+/// Average time of one call over `RUNS` calls.
+fn time<T>(mut run: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(run());
+    }
+    start.elapsed() / RUNS
+}
+
+fn report(name: &str, per_block: Duration) {
+    println!("  {:<32} {:?} per block", name, per_block);
+}
+
+/// Program id of every top-level and inner instruction of the block.
+fn program_ids(block: &Block) -> Vec<&[u8]> {
+    let mut program_ids = Vec::new();
+    for trx in &block.transactions {
+        let message = trx.transaction.as_ref().and_then(|t| t.message.as_ref());
+        let (Some(meta), Some(message)) = (&trx.meta, message) else {
+            continue;
+        };
+        let keys: Vec<&[u8]> = message
+            .account_keys
+            .iter()
+            .chain(&meta.loaded_writable_addresses)
+            .chain(&meta.loaded_readonly_addresses)
+            .map(Vec::as_slice)
+            .collect();
+        let inner = meta.inner_instructions.iter().flat_map(|inner| &inner.instructions).map(|i| i.program_id_index);
+        for index in message.instructions.iter().map(|i| i.program_id_index).chain(inner) {
+            if let Some(key) = keys.get(index as usize) {
+                program_ids.push(*key);
+            }
+        }
+    }
+    program_ids
+}
+
+fn main() {
+    let programs: Vec<&str> = JUPITER_PROGRAM_IDS.into_iter().chain([LIMIT_ORDER_PROGRAM_ID, DCA_PROGRAM_ID]).collect();
+    let by_string: HashSet<&str> = programs.iter().copied().collect();
+    let by_key: Vec<Vec<u8>> = programs.iter().map(|id| base58::decode(id).unwrap()).collect();
+
+    let mut fixtures: Vec<_> = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "binpb"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixture blocks in {}", FIXTURES);
+
+    for fixture in fixtures {
+        let block = Block::decode(fs::read(&fixture).unwrap().as_slice()).unwrap();
+        let ids = program_ids(&block);
+        let name = fixture.file_stem().unwrap().to_string_lossy();
+
+        let strings = || ids.iter().filter(|id| by_string.contains(base58::encode(id).as_str())).count();
+        let keys = || ids.iter().filter(|id| by_key.iter().any(|key| key == *id)).count();
+        assert_eq!(strings(), keys());
+        println!("{}: {} instructions, {} Jupiter calls", name, ids.len(), keys());
+        report("base58 strings", time(strings));
+        report("raw keys", time(keys));
+
+        let filtered = filtered_block::__impl_map_filtered_block(String::new(), block.clone()).unwrap();
+        report("index_programs", time(|| block_index::__impl_index_programs(block.clone())));
+        report("map_filtered_block", time(|| filtered_block::__impl_map_filtered_block(String::new(), block.clone())));
+        report(
+            "map_transaction_classifications",
+            time(|| classification::__impl_map_transaction_classifications(String::new(), filtered.clone())),
+        );
+        report("map_swap_routes", time(|| swap_routes::__impl_map_swap_routes(String::new(), filtered.clone())));
+        report("map_order_events", time(|| order_events::__impl_map_order_events(filtered.clone())));
+    }
+}
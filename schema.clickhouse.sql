@@ -0,0 +1,170 @@
+-- ClickHouse schema for db_out, shipped by substreams.clickhouse.yaml. Columns match
+-- the fields db_out sets; row_key holds the primary key of tables whose key is not one
+-- of their columns. ReplacingMergeTree collapses rows replayed after a reorg or restart.
+-- candles and the daily_* rollups are not listed: the SQL sink only applies upserts on
+-- Postgres, so ClickHouse deployments exclude them and aggregate the raw tables instead.
+
+CREATE TABLE IF NOT EXISTS wallet_balance_changes (
+    row_key                 String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    tx_id                   String,
+    owner                   String,
+    mint                    String,
+    change_amount           Decimal(38, 18),
+    new_balance             Decimal(38, 18),
+    raw_new_balance         UInt64,
+    decimals                UInt32,
+    change_type             String,
+    tx_change_type          String,
+    labels                  String,
+    invoker_label           String,
+    cpi_path                String,
+    category                String,
+    network_fee             Float64,
+    tx_index                UInt32,
+    instruction_index       Int32,
+    inner_instruction_index Int32,
+    stack_height            UInt32,
+    id                      String,
+    token_account           String,
+    account_index           UInt32,
+    native                  Bool
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (row_key);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    tx_id                   String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    fee_payer               String,
+    signers                 String,
+    success                 Bool,
+    fee                     Float64,
+    compute_units_consumed  UInt64,
+    programs                String,
+    jupiter_version         String,
+    change_type             String,
+    labels                  String,
+    invoker_label           String,
+    tx_index                UInt32,
+    id                      String
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (tx_id);
+
+CREATE TABLE IF NOT EXISTS swaps (
+    row_key                 String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    tx_id                   String,
+    trader                  String,
+    change_type             String,
+    sold_mint               String,
+    sold_amount             Float64,
+    bought_mint             String,
+    bought_amount           Float64,
+    effective_price         Float64,
+    network_fee             Float64,
+    rent_adjustment         Float64,
+    tx_index                UInt32,
+    instruction_index       Int32,
+    inner_instruction_index Int32,
+    stack_height            UInt32,
+    id                      String
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (row_key);
+
+CREATE TABLE IF NOT EXISTS swap_routes (
+    id                      String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    tx_id                   String,
+    trader                  String,
+    route_id                String,
+    hop_index               UInt32,
+    program_id              String,
+    dex                     String,
+    input_mint              String,
+    input_amount            UInt64,
+    output_mint             String,
+    output_amount           UInt64,
+    tx_index                UInt32,
+    instruction_index       Int32,
+    inner_instruction_index Int32,
+    stack_height            UInt32
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (id);
+
+CREATE TABLE IF NOT EXISTS limit_orders (
+    id                      String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    tx_id                   String,
+    program_id              String,
+    event                   String,
+    owner                   String,
+    order_account           String,
+    status                  String,
+    accounts                String,
+    in_amount               UInt64,
+    out_amount              UInt64,
+    in_amount_per_cycle     UInt64,
+    cycle_frequency         Int64,
+    tx_index                UInt32,
+    instruction_index       Int32,
+    inner_instruction_index Int32,
+    stack_height            UInt32
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (id);
+
+CREATE TABLE IF NOT EXISTS dca_events (
+    id                      String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    tx_id                   String,
+    program_id              String,
+    event                   String,
+    owner                   String,
+    order_account           String,
+    status                  String,
+    accounts                String,
+    in_amount               UInt64,
+    out_amount              UInt64,
+    in_amount_per_cycle     UInt64,
+    cycle_frequency         Int64,
+    tx_index                UInt32,
+    instruction_index       Int32,
+    inner_instruction_index Int32,
+    stack_height            UInt32
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (id);
+
+CREATE TABLE IF NOT EXISTS token_prices (
+    row_key                 String,
+    block_date              Date,
+    block_time              UInt64,
+    block_slot              UInt64,
+    mint                    String,
+    price_usd               Float64
+)
+ENGINE = ReplacingMergeTree(block_slot)
+PARTITION BY toYYYYMM(block_date)
+ORDER BY (row_key);
@@ -0,0 +1,200 @@
+-- Postgres schema for db_out. Columns match the fields db_out sets; row_key holds the
+-- primary key of tables whose key is not one of their columns.
+
+CREATE TABLE IF NOT EXISTS wallet_balance_changes (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    tx_id                   TEXT NOT NULL,
+    owner                   TEXT NOT NULL,
+    mint                    TEXT NOT NULL,
+    change_amount           NUMERIC NOT NULL,
+    new_balance             NUMERIC NOT NULL,
+    raw_new_balance         NUMERIC(20, 0) NOT NULL,
+    decimals                INTEGER NOT NULL,
+    change_type             TEXT NOT NULL,
+    tx_change_type          TEXT NOT NULL,
+    labels                  TEXT NOT NULL,
+    invoker_label           TEXT NOT NULL,
+    cpi_path                TEXT NOT NULL,
+    category                TEXT NOT NULL,
+    network_fee             DOUBLE PRECISION NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    instruction_index       INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    stack_height            INTEGER NOT NULL,
+    id                      TEXT NOT NULL,
+    token_account           TEXT NOT NULL,
+    account_index           INTEGER NOT NULL,
+    native                  BOOLEAN NOT NULL
+);
+CREATE INDEX IF NOT EXISTS wallet_balance_changes_owner_mint ON wallet_balance_changes (owner, mint, block_slot);
+CREATE INDEX IF NOT EXISTS wallet_balance_changes_tx_id ON wallet_balance_changes (tx_id);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    tx_id                   TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    fee_payer               TEXT NOT NULL,
+    signers                 TEXT NOT NULL,
+    success                 BOOLEAN NOT NULL,
+    fee                     DOUBLE PRECISION NOT NULL,
+    compute_units_consumed  BIGINT NOT NULL,
+    programs                TEXT NOT NULL,
+    jupiter_version         TEXT NOT NULL,
+    change_type             TEXT NOT NULL,
+    labels                  TEXT NOT NULL,
+    invoker_label           TEXT NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    id                      TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transactions_block_slot ON transactions (block_slot);
+
+CREATE TABLE IF NOT EXISTS swaps (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    tx_id                   TEXT NOT NULL,
+    trader                  TEXT NOT NULL,
+    change_type             TEXT NOT NULL,
+    sold_mint               TEXT NOT NULL,
+    sold_amount             DOUBLE PRECISION NOT NULL,
+    bought_mint             TEXT NOT NULL,
+    bought_amount           DOUBLE PRECISION NOT NULL,
+    effective_price         DOUBLE PRECISION NOT NULL,
+    network_fee             DOUBLE PRECISION NOT NULL,
+    rent_adjustment         DOUBLE PRECISION NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    instruction_index       INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    stack_height            INTEGER NOT NULL,
+    id                      TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS swaps_trader ON swaps (trader, block_slot);
+
+CREATE TABLE IF NOT EXISTS swap_routes (
+    id                      TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    tx_id                   TEXT NOT NULL,
+    trader                  TEXT NOT NULL,
+    route_id                TEXT NOT NULL,
+    hop_index               INTEGER NOT NULL,
+    program_id              TEXT NOT NULL,
+    dex                     TEXT NOT NULL,
+    input_mint              TEXT NOT NULL,
+    input_amount            NUMERIC(20, 0) NOT NULL,
+    output_mint             TEXT NOT NULL,
+    output_amount           NUMERIC(20, 0) NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    instruction_index       INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    stack_height            INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS swap_routes_route_id ON swap_routes (route_id, hop_index);
+
+CREATE TABLE IF NOT EXISTS limit_orders (
+    id                      TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    tx_id                   TEXT NOT NULL,
+    program_id              TEXT NOT NULL,
+    event                   TEXT NOT NULL,
+    owner                   TEXT NOT NULL,
+    order_account           TEXT NOT NULL,
+    status                  TEXT NOT NULL,
+    accounts                TEXT NOT NULL,
+    in_amount               NUMERIC(20, 0) NOT NULL,
+    out_amount              NUMERIC(20, 0) NOT NULL,
+    in_amount_per_cycle     NUMERIC(20, 0) NOT NULL,
+    cycle_frequency         BIGINT NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    instruction_index       INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    stack_height            INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS limit_orders_owner ON limit_orders (owner, block_slot);
+CREATE INDEX IF NOT EXISTS limit_orders_order_account ON limit_orders (order_account, block_slot);
+
+CREATE TABLE IF NOT EXISTS dca_events (
+    id                      TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    tx_id                   TEXT NOT NULL,
+    program_id              TEXT NOT NULL,
+    event                   TEXT NOT NULL,
+    owner                   TEXT NOT NULL,
+    order_account           TEXT NOT NULL,
+    status                  TEXT NOT NULL,
+    accounts                TEXT NOT NULL,
+    in_amount               NUMERIC(20, 0) NOT NULL,
+    out_amount              NUMERIC(20, 0) NOT NULL,
+    in_amount_per_cycle     NUMERIC(20, 0) NOT NULL,
+    cycle_frequency         BIGINT NOT NULL,
+    tx_index                INTEGER NOT NULL,
+    instruction_index       INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    stack_height            INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS dca_events_owner ON dca_events (owner, block_slot);
+CREATE INDEX IF NOT EXISTS dca_events_order_account ON dca_events (order_account, block_slot);
+
+CREATE TABLE IF NOT EXISTS token_prices (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    block_time              BIGINT NOT NULL,
+    block_slot              BIGINT NOT NULL,
+    mint                    TEXT NOT NULL,
+    price_usd               DOUBLE PRECISION NOT NULL
+);
+CREATE INDEX IF NOT EXISTS token_prices_mint ON token_prices (mint, block_slot);
+
+-- Written with upserts: every block that trades a mint rewrites its open candles.
+CREATE TABLE IF NOT EXISTS candles (
+    row_key                 TEXT PRIMARY KEY,
+    mint                    TEXT NOT NULL,
+    interval                TEXT NOT NULL,
+    start_time              BIGINT NOT NULL,
+    open                    DOUBLE PRECISION NOT NULL,
+    high                    DOUBLE PRECISION NOT NULL,
+    low                     DOUBLE PRECISION NOT NULL,
+    close                   DOUBLE PRECISION NOT NULL,
+    volume_usd              DOUBLE PRECISION NOT NULL,
+    block_slot              BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS candles_mint_interval ON candles (mint, interval, start_time);
+
+-- Daily rollups, upserted with the running totals of store_daily_totals.
+CREATE TABLE IF NOT EXISTS daily_mint_volumes (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    mint                    TEXT NOT NULL,
+    volume                  DOUBLE PRECISION NOT NULL DEFAULT 0,
+    volume_usd              DOUBLE PRECISION NOT NULL DEFAULT 0,
+    swap_legs               DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS daily_mint_volumes_mint ON daily_mint_volumes (mint, block_date);
+
+CREATE TABLE IF NOT EXISTS daily_wallet_flows (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    owner                   TEXT NOT NULL,
+    mint                    TEXT NOT NULL,
+    net_flow                DOUBLE PRECISION NOT NULL DEFAULT 0,
+    inflow                  DOUBLE PRECISION NOT NULL DEFAULT 0,
+    outflow                 DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS daily_wallet_flows_owner ON daily_wallet_flows (owner, block_date);
+
+CREATE TABLE IF NOT EXISTS daily_fees (
+    row_key                 TEXT PRIMARY KEY,
+    block_date              DATE NOT NULL,
+    network_fees            DOUBLE PRECISION NOT NULL DEFAULT 0,
+    fee_payments            DOUBLE PRECISION NOT NULL DEFAULT 0
+);
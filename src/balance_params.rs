@@ -0,0 +1,400 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::classification::{Category, Classifier};
+use crate::cost_basis::is_usd_stable;
+use crate::pb::sf::jupiter::v1::BalanceChange;
+use substreams::errors::Error;
+
+/// Parsed `map_balance_changes` params, read once per block from a querystring such as
+/// `owners=A,B&exclude_mints=C&change_types=SWAP&min_usd=10&sol=raw`. Values are
+/// comma-separated and repeated keys accumulate. A segment without `=` is read as an
+/// owner list, which keeps the original comma-separated whitelist working.
+///
+/// - `owners`: owner whitelist.
+/// - `watchlist=true|false`: also tracks wallets in `store_watchlist`; when enabled, only
+///   `owners` and watched wallets are tracked.
+/// - `mints` / `exclude_mints`: mint allow and deny lists.
+/// - `change_types` / `exclude_change_types`: change type prefixes, so `SWAP` matches
+///   `SWAP_JUPITER`.
+/// - `min_amount`, `min_amount.<mint>`: minimum absolute change in UI units; the per-mint
+///   value wins.
+/// - `min_usd`: minimum absolute USD value. Only stablecoins and mints swapped against a
+///   stablecoin in the same transaction have a known value; other rows are kept.
+/// - `native_sol=true|false`: drops native SOL rows (including net SOL rows) when false.
+/// - `sol=net|raw`: `raw` keeps native SOL and wSOL token legs as separate rows.
+/// - `aggregate=account|owner`: `owner` nets an owner's token accounts of one mint into
+///   one row per transaction; `token_account` is left empty when several accounts moved.
+/// - `from_slot`, `to_slot`, `from_time`, `to_time`: inclusive block range, times in unix
+///   seconds.
+/// - `label.<program_id>`, `category.<program_id>`, `priority.<LABEL_PREFIX>`:
+///   classification rules, see `Classifier::apply`. `category.` sets what a program does
+///   (`SWAP`, `NFT_TRADE`, `LEND`, `STAKE` or `PERP`, empty to clear it), which decides
+///   whether its legs count as swaps.
+///
+/// Filters drop rows before every module reading `map_balance_changes` sees them: trades,
+/// prices and candles, cost basis, daily totals, `db_out`, `graph_out`, `kv_out` and
+/// `files_out`. `native_sol=false` for instance also removes the `NETWORK_FEE` rows that
+/// trades and daily fees sum, and `mints=` leaves prices without the stablecoin legs
+/// they are derived from.
+pub(crate) struct BalanceParams {
+    pub owners: HashSet<String>,
+    pub watchlist: bool,
+    pub mints: HashSet<String>,
+    pub exclude_mints: HashSet<String>,
+    pub change_types: Vec<String>,
+    pub exclude_change_types: Vec<String>,
+    pub min_amount: Option<f64>,
+    pub min_amounts: HashMap<String, f64>,
+    pub min_usd: Option<f64>,
+    pub native_sol: bool,
+    pub keep_raw_sol_legs: bool,
+    pub aggregate_owners: bool,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+    pub from_time: Option<i64>,
+    pub to_time: Option<i64>,
+    pub classifier: Classifier,
+}
+
+impl Default for BalanceParams {
+    fn default() -> Self {
+        BalanceParams {
+            owners: HashSet::new(),
+            watchlist: false,
+            mints: HashSet::new(),
+            exclude_mints: HashSet::new(),
+            change_types: Vec::new(),
+            exclude_change_types: Vec::new(),
+            min_amount: None,
+            min_amounts: HashMap::new(),
+            min_usd: None,
+            native_sol: true,
+            keep_raw_sol_legs: false,
+            aggregate_owners: false,
+            from_slot: None,
+            to_slot: None,
+            from_time: None,
+            to_time: None,
+            classifier: Classifier::default(),
+        }
+    }
+}
+
+impl BalanceParams {
+    pub fn parse(params: &str) -> Result<Self, Error> {
+        let mut parsed = BalanceParams::default();
+
+        for segment in params.split('&').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = match segment.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => ("owners", segment),
+            };
+            let values = || value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+
+            match key {
+                "owners" => parsed.owners.extend(values()),
+                "mints" => parsed.mints.extend(values()),
+                "exclude_mints" => parsed.exclude_mints.extend(values()),
+                "change_types" => parsed.change_types.extend(values()),
+                "exclude_change_types" => parsed.exclude_change_types.extend(values()),
+                "min_amount" => parsed.min_amount = Some(parse_amount(key, value)?),
+                "min_usd" => parsed.min_usd = Some(parse_amount(key, value)?),
+                "watchlist" => parsed.watchlist = parse_bool(key, value)?,
+                "native_sol" => parsed.native_sol = parse_bool(key, value)?,
+                "sol" => {
+                    parsed.keep_raw_sol_legs = match value {
+                        "raw" => true,
+                        "net" => false,
+                        _ => return Err(invalid(key, value, "'raw' or 'net'")),
+                    }
+                }
+                "aggregate" => {
+                    parsed.aggregate_owners = match value {
+                        "owner" => true,
+                        "account" => false,
+                        _ => return Err(invalid(key, value, "'owner' or 'account'")),
+                    }
+                }
+                "from_slot" => parsed.from_slot = Some(parse_number(key, value)?),
+                "to_slot" => parsed.to_slot = Some(parse_number(key, value)?),
+                "from_time" => parsed.from_time = Some(parse_number(key, value)?),
+                "to_time" => parsed.to_time = Some(parse_number(key, value)?),
+                _ => {
+                    if let Some(mint) = key.strip_prefix("min_amount.") {
+                        parsed.min_amounts.insert(mint.to_string(), parse_amount(key, value)?);
+                    } else if !parsed.classifier.apply(key, value)? {
+                        return Err(anyhow::anyhow!("unknown map_balance_changes param '{}'", key));
+                    }
+                }
+            }
+        }
+
+        if let (Some(from), Some(to)) = (parsed.from_slot, parsed.to_slot) {
+            if from > to {
+                return Err(anyhow::anyhow!("from_slot {} is after to_slot {}", from, to));
+            }
+        }
+        if let (Some(from), Some(to)) = (parsed.from_time, parsed.to_time) {
+            if from > to {
+                return Err(anyhow::anyhow!("from_time {} is after to_time {}", from, to));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Whether the block falls within the slot and time range.
+    pub fn includes_block(&self, slot: u64, timestamp: i64) -> bool {
+        self.from_slot.is_none_or(|from| slot >= from)
+            && self.to_slot.is_none_or(|to| slot <= to)
+            && self.from_time.is_none_or(|from| timestamp >= from)
+            && self.to_time.is_none_or(|to| timestamp <= to)
+    }
+
+    /// Whether an owner is tracked; `watched` is only consulted with `watchlist=true`.
+    pub fn includes_owner(&self, owner: &str, watched: impl Fn(&str) -> bool) -> bool {
+        match self.watchlist {
+            true => self.owners.contains(owner) || watched(owner),
+            false => self.owners.is_empty() || self.owners.contains(owner),
+        }
+    }
+
+    /// Applies the mint, change type and minimum amount filters to one transaction's
+    /// changes.
+    pub fn filter(&self, changes: Vec<BalanceChange>) -> Vec<BalanceChange> {
+        let usd_prices = match self.min_usd {
+            Some(_) => usd_prices(&changes),
+            None => HashMap::new(),
+        };
+
+        let keep: Vec<bool> = changes
+            .iter()
+            .map(|change| {
+                let amount = change.change_amount.parse::<f64>().unwrap_or(0.0).abs();
+                let usd_value = usd_prices.get(&(change.owner.as_str(), change.mint.as_str())).map(|price| amount * price);
+                self.includes_mint(&change.mint)
+                    && self.includes_change_type(&change.change_type)
+                    && amount >= self.min_amounts.get(&change.mint).copied().or(self.min_amount).unwrap_or(0.0)
+                    && match (self.min_usd, usd_value) {
+                        (Some(min_usd), Some(usd_value)) => usd_value >= min_usd,
+                        _ => true,
+                    }
+            })
+            .collect();
+
+        changes.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(change, _)| change).collect()
+    }
+
+    fn includes_mint(&self, mint: &str) -> bool {
+        (self.mints.is_empty() || self.mints.contains(mint)) && !self.exclude_mints.contains(mint)
+    }
+
+    fn includes_change_type(&self, change_type: &str) -> bool {
+        let matches = |prefixes: &[String]| prefixes.iter().any(|prefix| change_type.starts_with(prefix.as_str()));
+        (self.change_types.is_empty() || matches(&self.change_types)) && !matches(&self.exclude_change_types)
+    }
+}
+
+/// USD price per `(owner, mint)` within one transaction: 1.0 for stablecoins, and for an
+/// owner's single non-stable swap leg, the stablecoin flow on the other side of it.
+fn usd_prices(changes: &[BalanceChange]) -> HashMap<(&str, &str), f64> {
+    let mut prices = HashMap::new();
+    let mut swaps: HashMap<&str, HashMap<&str, f64>> = HashMap::new();
+
+    for change in changes {
+        if is_usd_stable(&change.mint) {
+            prices.insert((change.owner.as_str(), change.mint.as_str()), 1.0);
+        }
+        if change.category == Category::Swap.as_str() {
+            let amount = change.change_amount.parse::<f64>().unwrap_or(0.0);
+            *swaps.entry(change.owner.as_str()).or_default().entry(change.mint.as_str()).or_default() += amount;
+        }
+    }
+
+    for (owner, legs) in swaps {
+        let (stable, other): (Vec<_>, Vec<_>) = legs.iter().partition(|(mint, _)| is_usd_stable(mint));
+        let (mint, amount) = match other.as_slice() {
+            [(mint, amount)] if **amount != 0.0 => (**mint, **amount),
+            _ => continue,
+        };
+        let stable_amount: f64 = stable
+            .iter()
+            .filter(|(_, stable)| stable.signum() != amount.signum())
+            .map(|(_, stable)| stable.abs())
+            .sum();
+        if stable_amount > 0.0 {
+            prices.insert((owner, mint), stable_amount / amount.abs());
+        }
+    }
+
+    prices
+}
+
+fn parse_amount(key: &str, value: &str) -> Result<f64, Error> {
+    match value.parse::<f64>() {
+        Ok(amount) if amount.is_finite() && amount >= 0.0 => Ok(amount),
+        _ => Err(invalid(key, value, "a non-negative number")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| invalid(key, value, "an integer"))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, Error> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(invalid(key, value, "'true' or 'false'")),
+    }
+}
+
+fn invalid(key: &str, value: &str, expected: &str) -> Error {
+    anyhow::anyhow!("invalid map_balance_changes param '{}={}': expected {}", key, value, expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program_keys::program_key;
+
+    const OWNER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn change(mint: &str, change_type: &str, amount: &str) -> BalanceChange {
+        BalanceChange {
+            owner: OWNER.to_string(),
+            mint: mint.to_string(),
+            change_type: change_type.to_string(),
+            change_amount: amount.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn swap(mint: &str, amount: &str) -> BalanceChange {
+        BalanceChange { category: "SWAP".to_string(), ..change(mint, "SWAP_JUPITER", amount) }
+    }
+
+    #[test]
+    fn empty_params_use_defaults() {
+        let params = BalanceParams::parse("").unwrap();
+        assert!(params.owners.is_empty());
+        assert!(!params.watchlist);
+        assert!(params.native_sol);
+        assert!(!params.keep_raw_sol_legs);
+        assert!(params.includes_block(0, 0));
+    }
+
+    #[test]
+    fn bare_list_is_an_owner_whitelist() {
+        let params = BalanceParams::parse("A, B,,C").unwrap();
+        assert_eq!(params.owners, HashSet::from(["A".to_string(), "B".to_string(), "C".to_string()]));
+        assert!(params.includes_owner("B", |_| false));
+        assert!(!params.includes_owner("D", |_| true));
+    }
+
+    #[test]
+    fn watchlist_adds_watched_owners_only() {
+        let everyone = BalanceParams::parse("").unwrap();
+        assert!(everyone.includes_owner("A", |_| false));
+
+        let watched = BalanceParams::parse("watchlist=true").unwrap();
+        assert!(!watched.includes_owner("A", |_| false));
+        assert!(watched.includes_owner("A", |owner| owner == "A"));
+
+        let both = BalanceParams::parse("owners=B&watchlist=true").unwrap();
+        assert!(both.includes_owner("B", |_| false));
+        assert!(both.includes_owner("A", |owner| owner == "A"));
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let params = BalanceParams::parse(
+            "owners=A&owners=B&mints=M1,M2&exclude_mints=M3&change_types=SWAP&exclude_change_types=RENT\
+             &min_amount=0.5&min_amount.M1=10&min_usd=25&native_sol=false&sol=raw&aggregate=owner\
+             &from_slot=100&to_slot=200&from_time=1700000000&to_time=1800000000",
+        )
+        .unwrap();
+
+        assert_eq!(params.owners.len(), 2);
+        assert_eq!(params.mints.len(), 2);
+        assert!(params.exclude_mints.contains("M3"));
+        assert_eq!(params.change_types, vec!["SWAP".to_string()]);
+        assert_eq!(params.exclude_change_types, vec!["RENT".to_string()]);
+        assert_eq!(params.min_amount, Some(0.5));
+        assert_eq!(params.min_amounts.get("M1"), Some(&10.0));
+        assert_eq!(params.min_usd, Some(25.0));
+        assert!(!params.native_sol);
+        assert!(params.keep_raw_sol_legs);
+        assert!(params.aggregate_owners);
+        assert!(params.includes_block(150, 1750000000));
+        assert!(!params.includes_block(99, 1750000000));
+        assert!(!params.includes_block(150, 1900000000));
+    }
+
+    #[test]
+    fn accepts_classification_rules() {
+        let params = BalanceParams::parse("priority.SWAP=9&label.JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4=SWAP_JUP").unwrap();
+        let jupiter = program_key("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+        assert_eq!(params.classifier.classify(&jupiter), Some(("SWAP_JUP", 9)));
+    }
+
+    #[test]
+    fn rejects_malformed_params() {
+        for params in [
+            "unknown=1",
+            "sol=both",
+            "aggregate=wallet",
+            "native_sol=maybe",
+            "min_amount=-1",
+            "min_usd=abc",
+            "from_slot=ten",
+            "from_slot=10&to_slot=5",
+            "from_time=10&to_time=5",
+            "priority.SWAP=300",
+            "label.not-a-program=SWAP",
+        ] {
+            assert!(BalanceParams::parse(params).is_err(), "{} should be rejected", params);
+        }
+    }
+
+    #[test]
+    fn filters_mints_and_change_types() {
+        let params = BalanceParams::parse("exclude_mints=M2&change_types=SWAP&exclude_change_types=SWAP_ORCA").unwrap();
+        let kept = params.filter(vec![
+            change("M1", "SWAP_JUPITER", "1"),
+            change("M2", "SWAP_JUPITER", "1"),
+            change("M1", "SWAP_ORCA", "1"),
+            change("M1", "SEND", "1"),
+        ]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].mint, "M1");
+        assert_eq!(kept[0].change_type, "SWAP_JUPITER");
+    }
+
+    #[test]
+    fn filters_minimum_amounts() {
+        let params = BalanceParams::parse("min_amount=1&min_amount.M2=100").unwrap();
+        let kept = params.filter(vec![
+            change("M1", "SEND", "-0.5"),
+            change("M1", "SEND", "-2"),
+            change("M2", "SEND", "50"),
+            change("M2", "SEND", "150"),
+        ]);
+        let amounts: Vec<&str> = kept.iter().map(|c| c.change_amount.as_str()).collect();
+        assert_eq!(amounts, vec!["-2", "150"]);
+    }
+
+    #[test]
+    fn filters_minimum_usd_when_priced() {
+        let params = BalanceParams::parse("min_usd=10").unwrap();
+        let kept = params.filter(vec![
+            swap(USDC, "-5"),
+            swap("M1", "20"),
+            change("M2", "SEND", "1"),
+        ]);
+        // The swap is worth $5 on both legs; the unpriced transfer is kept.
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].mint, "M2");
+    }
+}
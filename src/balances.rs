@@ -1,56 +1,29 @@
 use substreams_solana::pb::sf::solana::r#type::v1 as solana;
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
 use crate::pb::sf::jupiter::v1::{BalanceChange, BalanceChanges};
 use std::collections::{HashMap, HashSet};
 use chrono::{DateTime};
 use substreams_solana::base58;
-
-// --- CONSTANTS ---
-const WRAPPED_SOL_MINT: &str = "So11111111111111111111111111111111111111112";
-const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
-
-// --- PROGRAM IDS ---
-
-// JUPITER AGGREGATOR (All Versions)
-const JUPITER_V6_PROGRAM: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
-const JUPITER_V4_PROGRAM: &str = "JUP4Fb2cqiUsePSIwy4c6inR7aUBkRSd8kPU86hAhuq";
-const JUPITER_V3_PROGRAM: &str = "JUP3c2Uh3WA4Ng34tw6kPd2G4C5BB21Xo36Je1s32Ph";
-const JUPITER_V2_PROGRAM: &str = "JUP2jxvXaqu7NQY1GmNF4m1vodw12LVXYxbFL2uJvfo"; // <--- ADDED V2
-const JUPITER_LIMIT_PROGRAM: &str = "jupoNjAxXgZqQXzgFSSmu9cGIqmq8cz3vipr5Z9i9d";
-
-// OTHER DEX / SWAPS (Direct interactions)
-const PHOENIX_PROGRAM: &str = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY";
-const RAYDIUM_V4_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
-const ORCA_WHIRLPOOL_PROGRAM: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
-
-// NFT MARKETS
-const MAGIC_EDEN_V2_PROGRAM: &str = "M2mx93ekt1fmXSVkTrUL9xVFHkmME8HTUi5Cyc5aF7K";
-const TENSOR_SWAP_PROGRAM: &str = "TSWAPaqyCSx2KABkXpnVkqXVHkBNbLKeW8SagU25N1";
-
-// LENDING / BORROWING
-const KAMINO_LENDING_PROGRAM: &str = "KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD";
-const MARGINFI_V2_PROGRAM: &str = "MFv2hWf31Z9kbCa1snEPYctwafyhdvnV7FZnsebVac6";
-const SOLEND_PROGRAM: &str = "So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3ZUE8uB5y";
-
-// STAKING (Liquid & Native)
-const STAKE_PROGRAM: &str = "Stake11111111111111111111111111111111111111";
-const MARINADE_PROGRAM: &str = "MarBmsSgKXdrN1egZf5sqe1CJNPUbNEXRPn4nsPAafF";
-const JITO_STAKE_PROGRAM: &str = "Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb";
-
-// PERPETUALS
-const DRIFT_V2_PROGRAM: &str = "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH";
+use crate::balance_params::BalanceParams;
+use crate::classification::{Category, TxClassification};
+use crate::constants::{LAMPORTS_PER_SOL, WRAPPED_SOL_MINT};
+use crate::instructions::{account_keys, ordered_instructions, record_id, stack_height, InstructionRef, Movements};
+use crate::rent::{is_rent_transfer, rent_flows};
+use crate::watchlist::is_watched;
+use substreams::store::{StoreGet, StoreGetString};
+
+// --- CHANGE TYPES ---
+pub(crate) const RENT_DEPOSIT: &str = "RENT_DEPOSIT";
+pub(crate) const RENT_REFUND: &str = "RENT_REFUND";
+pub(crate) const NETWORK_FEE: &str = "NETWORK_FEE";
 
 #[substreams::handlers::map]
-pub fn map_balance_changes(params: String, block: solana::Block) -> Result<BalanceChanges, substreams::errors::Error> {
+pub fn map_balance_changes(params: String, block: FilteredBlock, watchlist: StoreGetString) -> Result<BalanceChanges, substreams::errors::Error> {
     let mut balance_changes = vec![];
 
-    // 0. Parse Whitelist
-    let whitelist: HashSet<String> = params
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    let use_whitelist = !whitelist.is_empty();
+    // 0. Parse Filters & Options
+    let params = BalanceParams::parse(&params)?;
+    let watched = |owner: &str| is_watched(&watchlist, owner);
 
     // Timestamp & Date
     let timestamp = block.block_time.as_ref().map(|t| t.timestamp).unwrap_or(0);
@@ -58,7 +31,11 @@ pub fn map_balance_changes(params: String, block: solana::Block) -> Result<Balan
     let block_date = dt.format("%Y-%m-%d").to_string();
     let slot = block.slot;
 
-    for trx in block.transactions {
+    if !params.includes_block(slot, timestamp) {
+        return Ok(BalanceChanges { params: balance_changes });
+    }
+
+    for (tx_index, trx) in block.indexed_transactions() {
         if let Some(meta) = &trx.meta {
             // 1. Skip Failed Transactions
             if meta.err.is_some() {
@@ -81,127 +58,110 @@ pub fn map_balance_changes(params: String, block: solana::Block) -> Result<Balan
                 None => continue,
             };
             let accounts = &message.account_keys;
+            let instructions = ordered_instructions(message, meta);
 
             // ---------------------------------------------------------
             // IDENTIFY TRANSACTION TYPE
             // ---------------------------------------------------------
 
-            let mut detected_type = "SEND".to_string();
-            let mut highest_priority = 0;
-
-            // Mapping Logic
-            let get_program_label = |prog_id: &str| -> Option<&str> {
-                match prog_id {
-                    // Jupiter Aggregator
-                    JUPITER_V6_PROGRAM |
-                    JUPITER_V4_PROGRAM |
-                    JUPITER_V3_PROGRAM |
-                    JUPITER_V2_PROGRAM | // <--- Matched here
-                    JUPITER_LIMIT_PROGRAM => Some("SWAP_JUPITER"),
-
-                    // Direct DEX Swaps
-                    PHOENIX_PROGRAM => Some("SWAP_PHOENIX"),
-                    RAYDIUM_V4_PROGRAM => Some("SWAP_RAYDIUM"),
-                    ORCA_WHIRLPOOL_PROGRAM => Some("SWAP_ORCA"),
-
-                    // NFT
-                    MAGIC_EDEN_V2_PROGRAM => Some("NFT_TRADE_MAGIC_EDEN"),
-                    TENSOR_SWAP_PROGRAM => Some("NFT_TRADE_TENSOR"),
-
-                    // Lending
-                    KAMINO_LENDING_PROGRAM => Some("LEND_KAMINO"),
-                    MARGINFI_V2_PROGRAM => Some("LEND_MARGINFI"),
-                    SOLEND_PROGRAM => Some("LEND_SOLEND"),
-
-                    // Staking
-                    STAKE_PROGRAM => Some("STAKE_NATIVE"),
-                    MARINADE_PROGRAM => Some("STAKE_MARINADE"),
-                    JITO_STAKE_PROGRAM => Some("STAKE_JITO"),
-
-                    // Perps
-                    DRIFT_V2_PROGRAM => Some("PERP_DRIFT"),
-
-                    _ => None,
-                }
+            let classification = TxClassification::new(&params.classifier, &instructions);
+            let detected_type = &classification.change_type;
+            let labels: Vec<String> = classification.labels.iter().map(|label| label.to_string()).collect();
+            let label_of = |at: InstructionRef| classification.label(at);
+            let priority_of = |at: InstructionRef| classification.priority(at);
+
+            let keys = account_keys(message, meta);
+            // Amounts in raw units (lamports for SOL), rendered as UI amounts.
+            let change = |account_index: usize, owner: &str, mint: &str, change_amount: i128, new_balance: i128, decimals: u32, change_type: String, at: InstructionRef| BalanceChange {
+                id: change_id(slot, tx_index, at, account_index, "native"),
+                block_date: block_date.clone(),
+                block_time: timestamp as u64,
+                block_slot: slot,
+                tx_id: tx_id.clone(),
+                owner: owner.to_string(),
+                mint: mint.to_string(),
+                change_amount: ui_amount(change_amount, decimals).to_string(),
+                new_balance: ui_amount(new_balance, decimals).to_string(),
+                raw_new_balance: new_balance.to_string(),
+                decimals,
+                change_type,
+                network_fee,
+                tx_change_type: detected_type.clone(),
+                instruction_index: at.index,
+                inner_instruction_index: at.inner_index,
+                labels: labels.clone(),
+                invoker_label: classification.invoker(at).to_string(),
+                cpi_path: classification.cpi_path(at),
+                tx_index,
+                stack_height: stack_height(&instructions, at),
+                token_account: keys.get(account_index).map(base58::encode).unwrap_or_default(),
+                account_index: account_index as u32,
+                native: true,
+                category: classification.category(at).map(Category::as_str).unwrap_or_default().to_string(),
             };
 
-            // Priority Logic
-            let mut check_instruction = |prog_index: usize| {
-                if prog_index < accounts.len() {
-                    let prog_id = base58::encode(&accounts[prog_index]);
-
-                    if let Some(label) = get_program_label(&prog_id) {
-                        let priority = match label {
-                            // NFT Trades are specific; usually override swaps
-                            s if s.starts_with("NFT") => 5,
-
-                            // Lending/Perps often use swaps internally, so we prioritize them
-                            // if the Lending Program is the top-level invoker.
-                            s if s.starts_with("LEND") => 4,
-                            s if s.starts_with("PERP") => 4,
-
-                            // Swaps
-                            s if s.starts_with("SWAP") => 3,
-
-                            // Staking
-                            s if s.starts_with("STAKE") => 2,
-
-                            _ => 1,
-                        };
-
-                        if priority > highest_priority {
-                            highest_priority = priority;
-                            detected_type = label.to_string();
-                        }
-                    }
-                }
-            };
-
-            // A. Top-Level Instructions
-            for inst in &message.instructions {
-                check_instruction(inst.program_id_index as usize);
-            }
-
-            // B. Inner Instructions (CPI)
-            for inner in &meta.inner_instructions {
-                for inst in &inner.instructions {
-                    check_instruction(inst.program_id_index as usize);
-                }
-            }
+            // wSOL token accounts touched by this transaction. Their lamports mirror the
+            // wrapped balance (plus rent), so in net mode they are folded into the owner's SOL.
+            let wsol_accounts: HashSet<usize> = meta
+                .pre_token_balances
+                .iter()
+                .chain(meta.post_token_balances.iter())
+                .filter(|b| b.mint == WRAPPED_SOL_MINT)
+                .map(|b| b.account_index as usize)
+                .collect();
+            let mut net_sol = NetSol::default();
 
             // ---------------------------------------------------------
             // 1. NATIVE SOL CHANGES
             // ---------------------------------------------------------
-            if meta.pre_balances.len() == meta.post_balances.len() {
+            // The transaction fee and rent moved by account creation and closing are
+            // reported as their own rows so they do not pollute transfers and swaps.
+            let tx_start = balance_changes.len();
+            let rent = rent_flows(&instructions, meta);
+            let native_movements = Movements::native(instructions.iter().filter(|inst| !is_rent_transfer(inst, &rent)));
+
+            if params.native_sol && meta.pre_balances.len() == meta.post_balances.len() {
                 for (i, pre_lamports) in meta.pre_balances.iter().enumerate() {
                     let post_lamports = meta.post_balances[i];
 
                     if *pre_lamports == post_lamports { continue; }
 
                     if i < accounts.len() {
+                        if !params.keep_raw_sol_legs && wsol_accounts.contains(&i) { continue; }
+
                         let address = base58::encode(&accounts[i]);
 
-                        if use_whitelist && !whitelist.contains(&address) { continue; }
-
-                        let pre_amt = *pre_lamports as f64 / LAMPORTS_PER_SOL;
-                        let post_amt = post_lamports as f64 / LAMPORTS_PER_SOL;
-
-                        // Dust filter
-                        if (post_amt - pre_amt).abs() < f64::EPSILON { continue; }
-
-                        balance_changes.push(BalanceChange {
-                            block_date: block_date.clone(),
-                            block_time: timestamp as u64,
-                            block_slot: slot,
-                            tx_id: tx_id.clone(),
-                            owner: address,
-                            mint: WRAPPED_SOL_MINT.to_string(),
-                            change_amount: (post_amt - pre_amt).to_string(),
-                            new_balance: post_amt.to_string(),
-                            decimals: 9,
-                            change_type: detected_type.clone(),
-                            network_fee,
-                        });
+                        if !params.includes_owner(&address, watched) { continue; }
+
+                        let post_lamports = post_lamports as i128;
+
+                        // The fee payer (account 0) gets an explicit fee row so its
+                        // economic change excludes the fee.
+                        let fee = if i == 0 { -(meta.fee as i128) } else { 0 };
+                        if fee != 0 {
+                            balance_changes.push(unclassified(change(i, &address, WRAPPED_SOL_MINT, fee, post_lamports, 9, NETWORK_FEE.to_string(), InstructionRef::NONE)));
+                        }
+
+                        let (deposit, deposit_at) = rent.deposit(i);
+                        let (refund, refund_at) = rent.refund(i);
+                        if deposit != 0 {
+                            balance_changes.push(unclassified(change(i, &address, WRAPPED_SOL_MINT, deposit, post_lamports, 9, RENT_DEPOSIT.to_string(), deposit_at)));
+                        }
+                        if refund != 0 {
+                            balance_changes.push(unclassified(change(i, &address, WRAPPED_SOL_MINT, refund, post_lamports, 9, RENT_REFUND.to_string(), refund_at)));
+                        }
+
+                        let residual = post_lamports - *pre_lamports as i128 - fee - deposit - refund;
+                        let parts = native_movements.split(i, residual);
+
+                        if !params.keep_raw_sol_legs {
+                            net_sol.add_native(&address, i, &parts, post_lamports);
+                            continue;
+                        }
+
+                        for ((at, amount), balance) in parts.iter().zip(running_balances(&parts, post_lamports)) {
+                            balance_changes.push(change(i, &address, WRAPPED_SOL_MINT, *amount, balance, 9, label_of(*at), *at));
+                        }
                     }
                 }
             }
@@ -209,44 +169,413 @@ pub fn map_balance_changes(params: String, block: solana::Block) -> Result<Balan
             // ---------------------------------------------------------
             // 2. SPL TOKEN CHANGES
             // ---------------------------------------------------------
-            let mut pre_balances: HashMap<(u32, String), f64> = HashMap::new();
+            let token_movements = Movements::token(&instructions);
+            let mut token_changes = vec![];
+            let mut owner_tokens = OwnerTokens::default();
+            let mut pre_balances: HashMap<(u32, String), i128> = HashMap::new();
 
             for balance in &meta.pre_token_balances {
-                let amount: f64 = balance.ui_token_amount.as_ref().map(|a| a.ui_amount).unwrap_or(0.0);
-                pre_balances.insert((balance.account_index, balance.mint.clone()), amount);
+                pre_balances.insert((balance.account_index, balance.mint.clone()), raw_amount(balance));
             }
 
             for post_balance in &meta.post_token_balances {
                 if post_balance.owner.is_empty() { continue; }
 
-                if use_whitelist && !whitelist.contains(&post_balance.owner) { continue; }
+                if !params.includes_owner(&post_balance.owner, watched) { continue; }
 
                 let account_idx = post_balance.account_index;
                 let mint = post_balance.mint.clone();
 
-                let post_amount: f64 = post_balance.ui_token_amount.as_ref().map(|a| a.ui_amount).unwrap_or(0.0);
+                let post_raw = raw_amount(post_balance);
+                let pre_raw = pre_balances.get(&(account_idx, mint.clone())).copied().unwrap_or(0);
+                if post_raw == pre_raw { continue; }
+
+                let parts = token_movements.split(account_idx as usize, post_raw - pre_raw);
+
+                if !params.keep_raw_sol_legs && mint == WRAPPED_SOL_MINT {
+                    net_sol.add_wrapped(&post_balance.owner, account_idx as usize, &parts, post_raw);
+                    continue;
+                }
+
                 let decimals = post_balance.ui_token_amount.as_ref().map(|a| a.decimals).unwrap_or(0);
 
-                let pre_amount = pre_balances.get(&(account_idx, mint.clone())).copied().unwrap_or(0.0);
-
-                if (post_amount - pre_amount).abs() > f64::EPSILON {
-                    balance_changes.push(BalanceChange {
-                        block_date: block_date.clone(),
-                        block_time:  timestamp as u64,
-                        block_slot: slot,
-                        tx_id: tx_id.clone(),
-                        owner: post_balance.owner.clone(),
-                        mint: mint,
-                        change_amount: (post_amount - pre_amount).to_string(),
-                        new_balance: post_amount.to_string(),
-                        decimals,
-                        change_type: detected_type.clone(),
-                        network_fee,
-                    });
+                if params.aggregate_owners {
+                    owner_tokens.add(&post_balance.owner, &mint, decimals, account_idx as usize, &parts, post_raw);
+                    continue;
+                }
+
+                for ((at, amount), balance) in parts.iter().zip(running_balances(&parts, post_raw)) {
+                    token_changes.push(token(change(account_idx as usize, &post_balance.owner, &mint, *amount, balance, decimals, label_of(*at), *at)));
+                }
+            }
+
+            // wSOL accounts closed by this transaction (unwraps) have no post balance, but
+            // their wrapped amount still left the owner's wSOL.
+            let post_indexes: HashSet<u32> = meta.post_token_balances.iter().map(|b| b.account_index).collect();
+            for pre_balance in meta.pre_token_balances.iter().filter(|b| b.mint == WRAPPED_SOL_MINT) {
+                if post_indexes.contains(&pre_balance.account_index) || pre_balance.owner.is_empty() { continue; }
+
+                if !params.includes_owner(&pre_balance.owner, watched) { continue; }
+
+                let pre_raw = raw_amount(pre_balance);
+                if pre_raw == 0 { continue; }
+
+                let parts = token_movements.split(pre_balance.account_index as usize, -pre_raw);
+
+                if !params.keep_raw_sol_legs {
+                    net_sol.add_wrapped(&pre_balance.owner, pre_balance.account_index as usize, &parts, 0);
+                    continue;
+                }
+
+                if params.aggregate_owners {
+                    owner_tokens.add(&pre_balance.owner, WRAPPED_SOL_MINT, 9, pre_balance.account_index as usize, &parts, 0);
+                    continue;
+                }
+
+                for ((at, amount), balance) in parts.iter().zip(running_balances(&parts, 0)) {
+                    token_changes.push(token(change(pre_balance.account_index as usize, &pre_balance.owner, WRAPPED_SOL_MINT, *amount, balance, 9, label_of(*at), *at)));
+                }
+            }
+
+            // Owner-aggregated mode: one row per owner and mint across its token accounts.
+            for (owner, mint, decimals, total) in owner_tokens.into_totals() {
+                if total.delta == 0 { continue; }
+                let at = total.instruction(priority_of);
+                let mut row = token(change(total.accounts[0], &owner, &mint, total.delta, total.post, decimals, label_of(at), at));
+                if total.accounts.len() > 1 {
+                    row.token_account = String::new();
                 }
+                token_changes.push(row);
             }
+
+            // ---------------------------------------------------------
+            // 3. NET SOL (native + wSOL) PER OWNER
+            // ---------------------------------------------------------
+            if params.native_sol {
+                for (owner, account, delta, post, at) in net_sol.into_rows(accounts, &meta.post_balances, priority_of) {
+                    balance_changes.push(change(account, &owner, WRAPPED_SOL_MINT, delta, post, 9, label_of(at), at));
+                }
+            }
+
+            balance_changes.extend(token_changes);
+
+            // Mint, change type and minimum amount filters, applied per transaction so
+            // USD values can come from the transaction's own swaps.
+            let tx_changes = balance_changes.split_off(tx_start);
+            balance_changes.extend(params.filter(tx_changes));
         }
     }
 
     Ok(BalanceChanges { params: balance_changes })
-}
\ No newline at end of file
+}
+
+/// Fee and rent rows carry their own change type, not the category of the instruction
+/// they are attributed to.
+fn unclassified(change: BalanceChange) -> BalanceChange {
+    let at = InstructionRef { index: change.instruction_index, inner_index: change.inner_instruction_index };
+    let kind = change.change_type.to_ascii_lowercase();
+    BalanceChange {
+        category: String::new(),
+        id: change_id(change.block_slot, change.tx_index, at, change.account_index as usize, &kind),
+        ..change
+    }
+}
+
+fn token(change: BalanceChange) -> BalanceChange {
+    let at = InstructionRef { index: change.instruction_index, inner_index: change.inner_instruction_index };
+    BalanceChange {
+        native: false,
+        id: change_id(change.block_slot, change.tx_index, at, change.account_index as usize, "token"),
+        ..change
+    }
+}
+
+/// `<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>:<account_index>:<kind>`,
+/// kind being `native`, `token`, `network_fee`, `rent_deposit` or `rent_refund`. The
+/// instruction alone is not unique: one instruction moves several accounts, and an
+/// account's native and wSOL rows can share it.
+fn change_id(slot: u64, tx_index: u32, at: InstructionRef, account_index: usize, kind: &str) -> String {
+    format!("{}:{}:{}", record_id(slot, tx_index, at), account_index, kind)
+}
+
+/// Balance after each part of a split change, given the balance after the last one.
+fn running_balances(parts: &[(InstructionRef, i128)], post: i128) -> Vec<i128> {
+    let mut balance = post;
+    let mut balances: Vec<i128> = parts
+        .iter()
+        .rev()
+        .map(|(_, amount)| {
+            let after = balance;
+            balance -= amount;
+            after
+        })
+        .collect();
+    balances.reverse();
+    balances
+}
+
+/// Per-owner SOL for one transaction in lamports, native and wrapped combined.
+#[derive(Default)]
+struct NetSol {
+    owners: Vec<String>,
+    totals: HashMap<String, OwnerTotal>,
+}
+
+/// One owner's balance summed across several accounts, split per top-level instruction.
+#[derive(Default)]
+struct OwnerTotal {
+    delta: i128,
+    post: i128,
+    has_native: bool,
+    /// Accounts that contributed, the one representing the row first.
+    accounts: Vec<usize>,
+    parts: Vec<(InstructionRef, i128)>,
+}
+
+impl OwnerTotal {
+    fn add(&mut self, account: usize, parts: &[(InstructionRef, i128)], post: i128) {
+        self.post += post;
+        if !self.accounts.contains(&account) {
+            self.accounts.push(account);
+        }
+        for (at, amount) in parts {
+            self.delta += amount;
+            match self.parts.iter_mut().find(|(part, _)| part.index == at.index) {
+                Some((_, total)) => *total += amount,
+                None => self.parts.push((*at, *amount)),
+            }
+        }
+    }
+
+    /// Moves spread over several instructions are attributed to the highest-priority
+    /// instruction involved, then the largest move.
+    fn instruction(&self, priority_of: impl Fn(InstructionRef) -> u8) -> InstructionRef {
+        self.parts
+            .iter()
+            .filter(|(_, amount)| *amount != 0)
+            .max_by_key(|(at, amount)| (priority_of(*at), amount.abs()))
+            .map(|(at, _)| *at)
+            .unwrap_or(InstructionRef::NONE)
+    }
+
+    fn put_first(&mut self, account: usize) {
+        self.accounts.retain(|existing| *existing != account);
+        self.accounts.insert(0, account);
+    }
+}
+
+impl NetSol {
+    fn add_native(&mut self, owner: &str, account: usize, parts: &[(InstructionRef, i128)], post: i128) {
+        let total = self.total(owner);
+        total.add(account, parts, post);
+        total.has_native = true;
+        total.put_first(account);
+    }
+
+    fn add_wrapped(&mut self, owner: &str, account: usize, parts: &[(InstructionRef, i128)], post: i128) {
+        self.total(owner).add(account, parts, post);
+    }
+
+    fn total(&mut self, owner: &str) -> &mut OwnerTotal {
+        if !self.totals.contains_key(owner) {
+            self.owners.push(owner.to_string());
+        }
+        self.totals.entry(owner.to_string()).or_default()
+    }
+
+    /// Returns `(owner, account, delta, post, instruction)` in first-seen order, skipping
+    /// owners that netted to zero. The account is the owner's wallet when it is part of
+    /// the transaction, else its first wSOL account. Owners that only moved wSOL get their
+    /// untouched native balance added to `post`.
+    fn into_rows(
+        mut self,
+        accounts: &[Vec<u8>],
+        post_balances: &[u64],
+        priority_of: impl Fn(InstructionRef) -> u8,
+    ) -> Vec<(String, usize, i128, i128, InstructionRef)> {
+        for owner in &self.owners {
+            let total = self.totals.get_mut(owner).expect("owner was recorded");
+            if total.has_native {
+                continue;
+            }
+            let key = base58::decode(owner).unwrap_or_default();
+            if let Some(index) = accounts.iter().position(|account| account == &key) {
+                total.post += post_balances.get(index).copied().unwrap_or(0) as i128;
+                total.put_first(index);
+            }
+        }
+
+        self.owners
+            .into_iter()
+            .filter_map(|owner| {
+                let total = self.totals.remove(&owner)?;
+                if total.delta == 0 {
+                    return None;
+                }
+                let at = total.instruction(&priority_of);
+                Some((owner, total.accounts[0], total.delta, total.post, at))
+            })
+            .collect()
+    }
+}
+
+/// Per owner and mint token totals for `aggregate=owner`, in first-seen order.
+#[derive(Default)]
+struct OwnerTokens {
+    order: Vec<(String, String)>,
+    totals: HashMap<(String, String), (u32, OwnerTotal)>,
+}
+
+impl OwnerTokens {
+    fn add(&mut self, owner: &str, mint: &str, decimals: u32, account: usize, parts: &[(InstructionRef, i128)], post: i128) {
+        let key = (owner.to_string(), mint.to_string());
+        if !self.totals.contains_key(&key) {
+            self.order.push(key.clone());
+        }
+        self.totals.entry(key).or_insert_with(|| (decimals, OwnerTotal::default())).1.add(account, parts, post);
+    }
+
+    /// Returns `(owner, mint, decimals, total)` in first-seen order.
+    fn into_totals(mut self) -> Vec<(String, String, u32, OwnerTotal)> {
+        self.order
+            .into_iter()
+            .filter_map(|key| {
+                let (decimals, total) = self.totals.remove(&key)?;
+                Some((key.0, key.1, decimals, total))
+            })
+            .collect()
+    }
+}
+
+fn ui_amount(raw: i128, decimals: u32) -> f64 {
+    raw as f64 / 10f64.powi(decimals as i32)
+}
+
+pub(crate) fn raw_amount(balance: &solana::TokenBalance) -> i128 {
+    balance
+        .ui_token_amount
+        .as_ref()
+        .and_then(|a| a.amount.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TOKEN_PROGRAM_ID;
+    use crate::instructions::TOKEN_TRANSFER;
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message, MessageHeader,
+        TokenBalance, Transaction, TransactionStatusMeta, UiTokenAmount,
+    };
+
+    fn token_balance(account_index: u32, amount: u64) -> TokenBalance {
+        TokenBalance {
+            account_index,
+            mint: base58::encode([5u8; 32]),
+            owner: base58::encode([1u8; 32]),
+            ui_token_amount: Some(UiTokenAmount {
+                ui_amount: amount as f64,
+                decimals: 0,
+                amount: amount.to_string(),
+                ui_amount_string: amount.to_string(),
+            }),
+            program_id: TOKEN_PROGRAM_ID.to_string(),
+        }
+    }
+
+    /// One top-level call to an unclassified program that CPIs into Kamino, which
+    /// transfers 10 tokens out of the user's account, and then into Marinade, which
+    /// transfers 4 back.
+    fn two_programs_under_one_instruction() -> Block {
+        let account_keys = vec![
+            vec![1u8; 32],
+            vec![2u8; 32],
+            vec![3u8; 32],
+            vec![4u8; 32],
+            vec![7u8; 32],
+            base58::decode("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD").unwrap(),
+            base58::decode("MarBmsSgKXdrN1egZf5sqe1CJNPUbNEXRPn4nsPAafF").unwrap(),
+            base58::decode(TOKEN_PROGRAM_ID).unwrap(),
+        ];
+        let inner = |program_id_index: u32, accounts: Vec<u8>, data: Vec<u8>, stack_height: u32| InnerInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height: Some(stack_height),
+        };
+        let transfer = |amount: u64| [vec![TOKEN_TRANSFER], amount.to_le_bytes().to_vec()].concat();
+
+        Block {
+            slot: 1,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys,
+                        instructions: vec![CompiledInstruction { program_id_index: 4, accounts: vec![0, 1, 2, 3], data: vec![] }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    fee: 5_000,
+                    pre_balances: vec![1_000_000_000, 1, 1, 1, 1, 1, 1, 1],
+                    post_balances: vec![999_995_000, 1, 1, 1, 1, 1, 1, 1],
+                    inner_instructions: vec![InnerInstructions {
+                        index: 0,
+                        instructions: vec![
+                            inner(5, vec![1, 2, 0], vec![], 2),
+                            inner(7, vec![1, 2, 0], transfer(10), 3),
+                            inner(6, vec![3, 1, 0], vec![], 2),
+                            inner(7, vec![3, 1, 0], transfer(4), 3),
+                        ],
+                    }],
+                    pre_token_balances: vec![token_balance(1, 100)],
+                    post_token_balances: vec![token_balance(1, 94)],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inner_transfers_keep_the_label_of_their_own_cpi() {
+        let user_account = base58::encode([2u8; 32]);
+        let rows: Vec<_> = __impl_map_balance_changes(String::new(), two_programs_under_one_instruction().into(), StoreGetString::new(0))
+            .unwrap()
+            .params
+            .into_iter()
+            .filter(|change| change.token_account == user_account)
+            .map(|change| (change.change_amount, change.change_type, change.instruction_index, change.inner_instruction_index))
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                ("-10".to_string(), "LEND_KAMINO".to_string(), 0, 1),
+                ("4".to_string(), "STAKE_MARINADE".to_string(), 0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_new_balance_keeps_every_digit() {
+        let mut block = two_programs_under_one_instruction();
+        let meta = block.transactions[0].meta.as_mut().unwrap();
+        let balance = |amount: u64| TokenBalance {
+            ui_token_amount: Some(UiTokenAmount { decimals: 9, ..token_balance(1, amount).ui_token_amount.unwrap() }),
+            ..token_balance(1, amount)
+        };
+        meta.pre_token_balances = vec![balance(u64::MAX)];
+        meta.post_token_balances = vec![balance(u64::MAX - 6)];
+
+        let changes = __impl_map_balance_changes(String::new(), block.into(), StoreGetString::new(0)).unwrap().params;
+        let user_account = base58::encode([2u8; 32]);
+        let rows: Vec<_> = changes.iter().filter(|change| change.token_account == user_account).collect();
+        // 18446744073.709551609 is past f64 precision; the raw balance is exact.
+        assert_eq!(rows.last().unwrap().new_balance, "18446744073.709553");
+        assert_eq!(rows.last().unwrap().raw_new_balance, "18446744073709551609");
+    }
+}
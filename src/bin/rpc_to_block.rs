@@ -0,0 +1,774 @@
+//! Converts saved Solana RPC responses into `sf.solana.type.v1.Block` protobuf files for
+//! the fixture harness in `tests/fixtures.rs`.
+//!
+//! ```text
+//! rpc_to_block [--slot <slot>] <output.binpb> <rpc.json>...
+//! ```
+//!
+//! Inputs are `getBlock` or `getTransaction` results, bare or inside the JSON-RPC
+//! envelope, fetched with `maxSupportedTransactionVersion: 0` and the `jsonParsed`,
+//! `json` or `base64` encoding. One `getBlock` file makes the whole block; several
+//! `getTransaction` files of one slot are put in one block in the order given.
+//!
+//! Parsed System, SPL Token, associated token account and memo instructions are encoded
+//! back to their binary form (Token-2022 extension instructions are not); any other parsed
+//! instruction fails the conversion, so fetch blocks with vote or other parsed programs in
+//! the `json` or `base64` encoding. Transaction errors are kept as their JSON text, only
+//! their presence matters.
+
+use std::collections::HashMap;
+use std::{env, fs, process};
+
+use prost::Message as _;
+use serde_json::Value;
+use substreams_solana::pb::sf::solana::r#type::v1::{
+    Block, BlockHeight, CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message,
+    MessageAddressTableLookup, MessageHeader, Reward, RewardType, TokenBalance, Transaction, TransactionError,
+    TransactionStatusMeta, UiTokenAmount, UnixTimestamp,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() {
+    if let Err(err) = run(env::args().skip(1).collect()) {
+        eprintln!("rpc_to_block: {}", err);
+        eprintln!("usage: rpc_to_block [--slot <slot>] <output.binpb> <rpc.json>...");
+        process::exit(1);
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<()> {
+    let mut slot = None;
+    if args.first().is_some_and(|arg| arg == "--slot") {
+        let value = args.get(1).ok_or("--slot needs a value")?;
+        slot = Some(value.parse::<u64>().map_err(|_| format!("invalid slot '{}'", value))?);
+        args.drain(..2);
+    }
+    if args.len() < 2 {
+        return Err("expected an output file and at least one RPC response".to_string());
+    }
+
+    let responses = args[1..]
+        .iter()
+        .map(|path| {
+            let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let json: Value = serde_json::from_str(&text).map_err(|err| format!("{}: {}", path, err))?;
+            Ok(json.get("result").cloned().unwrap_or(json))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let block = to_block(&responses, slot)?;
+    fs::write(&args[0], block.encode_to_vec()).map_err(|err| format!("{}: {}", args[0], err))?;
+    eprintln!("wrote slot {} with {} transactions to {}", block.slot, block.transactions.len(), args[0]);
+    Ok(())
+}
+
+fn to_block(responses: &[Value], slot: Option<u64>) -> Result<Block> {
+    match responses {
+        [block] if block.get("transactions").is_some() => {
+            // getBlock results do not name their own slot.
+            let slot = slot.or_else(|| block.get("slot").and_then(Value::as_u64));
+            let slot = slot.ok_or("getBlock results need --slot")?;
+            let transactions = block["transactions"]
+                .as_array()
+                .ok_or("transactions is not an array")?
+                .iter()
+                .enumerate()
+                .map(|(index, trx)| confirmed_transaction(trx).map_err(|err| format!("transaction {}: {}", index, err)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Block {
+                previous_blockhash: string(&block["previousBlockhash"]),
+                blockhash: string(&block["blockhash"]),
+                parent_slot: block["parentSlot"].as_u64().unwrap_or(0),
+                transactions,
+                rewards: rewards(&block["rewards"]),
+                block_time: block["blockTime"].as_i64().map(|timestamp| UnixTimestamp { timestamp }),
+                block_height: block["blockHeight"].as_u64().map(|block_height| BlockHeight { block_height }),
+                slot,
+            })
+        }
+        _ => {
+            let mut block = Block::default();
+            for (index, trx) in responses.iter().enumerate() {
+                if trx.get("transaction").is_none() {
+                    return Err(format!("input {} is neither a getBlock nor a getTransaction result", index));
+                }
+                let trx_slot = slot.or_else(|| trx["slot"].as_u64()).ok_or("getTransaction results need a slot")?;
+                if index > 0 && trx_slot != block.slot {
+                    return Err(format!("input {} is from slot {}, not {}", index, trx_slot, block.slot));
+                }
+                block.slot = trx_slot;
+                block.parent_slot = trx_slot.saturating_sub(1);
+                block.block_time = trx["blockTime"].as_i64().map(|timestamp| UnixTimestamp { timestamp });
+                block
+                    .transactions
+                    .push(confirmed_transaction(trx).map_err(|err| format!("input {}: {}", index, err))?);
+            }
+            Ok(block)
+        }
+    }
+}
+
+fn confirmed_transaction(trx: &Value) -> Result<ConfirmedTransaction> {
+    let meta = &trx["meta"];
+    let versioned = trx["version"].as_u64().is_some();
+    let loaded_writable = base58_list(&meta["loadedAddresses"]["writable"])?;
+    let loaded_readonly = base58_list(&meta["loadedAddresses"]["readonly"])?;
+
+    let transaction = match &trx["transaction"] {
+        Value::Array(encoded) => {
+            let (data, encoding) = (string(&encoded[0]), string(&encoded[1]));
+            if encoding != "base64" {
+                return Err(format!("unsupported transaction encoding '{}'", encoding));
+            }
+            wire_transaction(&base64(&data)?)?
+        }
+        Value::Object(_) => json_transaction(&trx["transaction"], versioned)?,
+        _ => return Err("missing transaction".to_string()),
+    };
+
+    // Instruction account indexes refer to static keys, then loaded writable, then loaded
+    // readonly addresses.
+    let message = transaction.message.as_ref().ok_or("missing message")?;
+    let keys: Vec<Vec<u8>> = message
+        .account_keys
+        .iter()
+        .chain(&loaded_writable)
+        .chain(&loaded_readonly)
+        .cloned()
+        .collect();
+    let indexes: HashMap<String, u8> = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (bs58::encode(key).into_string(), index as u8))
+        .collect();
+
+    let inner_instructions = meta["innerInstructions"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|inner| {
+            let instructions = inner["instructions"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(|inst| {
+                    let compiled = instruction(inst, &indexes)?;
+                    Ok(InnerInstruction {
+                        program_id_index: compiled.program_id_index,
+                        accounts: compiled.accounts,
+                        data: compiled.data,
+                        stack_height: inst["stackHeight"].as_u64().map(|height| height as u32),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(InnerInstructions { index: inner["index"].as_u64().unwrap_or(0) as u32, instructions })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let meta = TransactionStatusMeta {
+        err: match &meta["err"] {
+            Value::Null => None,
+            err => Some(TransactionError { err: err.to_string().into_bytes() }),
+        },
+        fee: meta["fee"].as_u64().unwrap_or(0),
+        pre_balances: u64_list(&meta["preBalances"]),
+        post_balances: u64_list(&meta["postBalances"]),
+        inner_instructions,
+        log_messages: meta["logMessages"]
+            .as_array()
+            .map(|logs| logs.iter().map(string).collect())
+            .unwrap_or_default(),
+        pre_token_balances: token_balances(&meta["preTokenBalances"]),
+        post_token_balances: token_balances(&meta["postTokenBalances"]),
+        loaded_writable_addresses: loaded_writable,
+        loaded_readonly_addresses: loaded_readonly,
+        compute_units_consumed: meta["computeUnitsConsumed"].as_u64(),
+        ..Default::default()
+    };
+
+    Ok(ConfirmedTransaction { transaction: Some(transaction), meta: Some(meta) })
+}
+
+/// A `json` or `jsonParsed` transaction. Parsed account keys also list the loaded
+/// addresses, which stay in the meta only.
+fn json_transaction(transaction: &Value, versioned: bool) -> Result<Transaction> {
+    let message = &transaction["message"];
+    let account_keys = message["accountKeys"].as_array().ok_or("accountKeys is not an array")?;
+    let static_keys: Vec<&Value> = account_keys
+        .iter()
+        .filter(|key| key.get("source").is_none_or(|source| source == "transaction"))
+        .collect();
+
+    let header = match message.get("header") {
+        Some(header) => MessageHeader {
+            num_required_signatures: header["numRequiredSignatures"].as_u64().unwrap_or(0) as u32,
+            num_readonly_signed_accounts: header["numReadonlySignedAccounts"].as_u64().unwrap_or(0) as u32,
+            num_readonly_unsigned_accounts: header["numReadonlyUnsignedAccounts"].as_u64().unwrap_or(0) as u32,
+        },
+        None => {
+            let flag = |key: &Value, name: &str| key[name].as_bool().unwrap_or(false);
+            let count = |signer: bool, writable: bool| {
+                static_keys.iter().filter(|key| flag(key, "signer") == signer && flag(key, "writable") == writable).count()
+            };
+            MessageHeader {
+                num_required_signatures: static_keys.iter().filter(|key| flag(key, "signer")).count() as u32,
+                num_readonly_signed_accounts: count(true, false) as u32,
+                num_readonly_unsigned_accounts: count(false, false) as u32,
+            }
+        }
+    };
+
+    let static_keys = static_keys
+        .iter()
+        .map(|key| base58(key.get("pubkey").unwrap_or(key)))
+        .collect::<Result<Vec<_>>>()?;
+    // Parsed instructions name their accounts; the loaded addresses come after the
+    // static keys, in the order the parsed key list gives them.
+    let indexes: HashMap<String, u8> = account_keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (string(key.get("pubkey").unwrap_or(key)), index as u8))
+        .collect();
+
+    Ok(Transaction {
+        signatures: transaction["signatures"]
+            .as_array()
+            .ok_or("signatures is not an array")?
+            .iter()
+            .map(base58)
+            .collect::<Result<Vec<_>>>()?,
+        message: Some(Message {
+            header: Some(header),
+            account_keys: static_keys,
+            recent_blockhash: base58(&message["recentBlockhash"])?,
+            instructions: message["instructions"]
+                .as_array()
+                .ok_or("instructions is not an array")?
+                .iter()
+                .map(|inst| instruction(inst, &indexes))
+                .collect::<Result<Vec<_>>>()?,
+            versioned,
+            address_table_lookups: message["addressTableLookups"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(|lookup| {
+                    Ok(MessageAddressTableLookup {
+                        account_key: base58(&lookup["accountKey"])?,
+                        writable_indexes: u64_list(&lookup["writableIndexes"]).into_iter().map(|i| i as u8).collect(),
+                        readonly_indexes: u64_list(&lookup["readonlyIndexes"]).into_iter().map(|i| i as u8).collect(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+        }),
+    })
+}
+
+/// An instruction in compiled (`programIdIndex`), partially decoded (`programId` with
+/// named accounts and base58 data) or parsed form.
+fn instruction(inst: &Value, indexes: &HashMap<String, u8>) -> Result<CompiledInstruction> {
+    if let Some(program_id_index) = inst["programIdIndex"].as_u64() {
+        return Ok(CompiledInstruction {
+            program_id_index: program_id_index as u32,
+            accounts: u64_list(&inst["accounts"]).into_iter().map(|i| i as u8).collect(),
+            data: bs58::decode(string(&inst["data"])).into_vec().map_err(|err| err.to_string())?,
+        });
+    }
+
+    let program_id = string(&inst["programId"]);
+    let index_of = |key: &str| indexes.get(key).copied().ok_or_else(|| format!("account {} is not in the keys", key));
+    let program_id_index = index_of(&program_id)? as u32;
+
+    let (accounts, data) = match inst.get("parsed") {
+        None => (
+            inst["accounts"].as_array().map(Vec::as_slice).unwrap_or_default().iter().map(|key| index_of(&string(key))).collect::<Result<Vec<_>>>()?,
+            bs58::decode(string(&inst["data"])).into_vec().map_err(|err| err.to_string())?,
+        ),
+        Some(parsed) => match encode_parsed(&string(&inst["program"]), parsed) {
+            Some((names, data)) => {
+                let info = &parsed["info"];
+                let accounts = names
+                    .iter()
+                    .filter_map(|name| info.get(*name).map(string))
+                    .map(|key| index_of(&key))
+                    .collect::<Result<Vec<_>>>()?;
+                (accounts, data)
+            }
+            None => {
+                return Err(format!(
+                    "cannot encode parsed {} instruction {}; fetch with the json or base64 encoding",
+                    program_id, parsed["type"]
+                ))
+            }
+        },
+    };
+
+    Ok(CompiledInstruction { program_id_index, accounts, data })
+}
+
+/// Account names, in instruction order, and binary data of a parsed System, SPL Token,
+/// associated token account or memo instruction. Optional accounts missing from `info`
+/// are skipped, and so are multisig signers and memo signers, which parsed instructions
+/// do not name.
+fn encode_parsed(program: &str, parsed: &Value) -> Option<(Vec<&'static str>, Vec<u8>)> {
+    if program == "spl-memo" {
+        return Some((Vec::new(), parsed.as_str()?.as_bytes().to_vec()));
+    }
+    let info = &parsed["info"];
+    let amount = |name: &str| -> Option<u64> {
+        match &info[name] {
+            Value::String(amount) => amount.parse().ok(),
+            value => value.as_u64(),
+        }
+    };
+    let token_amount = || Some((info["tokenAmount"]["amount"].as_str()?.parse::<u64>().ok()?, info["tokenAmount"]["decimals"].as_u64()? as u8));
+    let mut data = Vec::new();
+
+    let names: Vec<&'static str> = match (program, parsed["type"].as_str()?) {
+        ("system", "transfer") => {
+            data.extend(2u32.to_le_bytes());
+            data.extend(amount("lamports")?.to_le_bytes());
+            vec!["source", "destination"]
+        }
+        ("system", "createAccount") => {
+            data.extend(0u32.to_le_bytes());
+            data.extend(amount("lamports")?.to_le_bytes());
+            data.extend(amount("space")?.to_le_bytes());
+            data.extend(bs58::decode(info["owner"].as_str()?).into_vec().ok()?);
+            vec!["source", "newAccount"]
+        }
+        ("system", "createAccountWithSeed") => {
+            data.extend(3u32.to_le_bytes());
+            data.extend(bs58::decode(info["base"].as_str()?).into_vec().ok()?);
+            let seed = info["seed"].as_str()?;
+            data.extend((seed.len() as u64).to_le_bytes());
+            data.extend(seed.as_bytes());
+            data.extend(amount("lamports")?.to_le_bytes());
+            data.extend(amount("space")?.to_le_bytes());
+            data.extend(bs58::decode(info["owner"].as_str()?).into_vec().ok()?);
+            vec!["source", "newAccount", "base"]
+        }
+        ("system", "transferWithSeed") => {
+            data.extend(11u32.to_le_bytes());
+            data.extend(amount("lamports")?.to_le_bytes());
+            let seed = info["sourceSeed"].as_str()?;
+            data.extend((seed.len() as u64).to_le_bytes());
+            data.extend(seed.as_bytes());
+            data.extend(bs58::decode(info["sourceOwner"].as_str()?).into_vec().ok()?);
+            vec!["source", "sourceBase", "destination"]
+        }
+        ("spl-associated-token-account", kind) => {
+            match kind {
+                "create" => {}
+                "createIdempotent" => data.push(1),
+                _ => return None,
+            }
+            vec!["source", "account", "wallet", "mint", "systemProgram", "tokenProgram", "rentSysvar"]
+        }
+        ("spl-token" | "spl-token-2022", kind) => {
+            let authority = |names: &[&'static str]| {
+                let mut names = names.to_vec();
+                names.extend(["authority", "multisigAuthority", "mintAuthority", "multisigMintAuthority", "owner", "multisigOwner"]);
+                names
+            };
+            match kind {
+                "transfer" => {
+                    data.push(3);
+                    data.extend(amount("amount")?.to_le_bytes());
+                    authority(&["source", "destination"])
+                }
+                "transferChecked" => {
+                    let (amount, decimals) = token_amount()?;
+                    data.push(12);
+                    data.extend(amount.to_le_bytes());
+                    data.push(decimals);
+                    authority(&["source", "mint", "destination"])
+                }
+                "mintTo" => {
+                    data.push(7);
+                    data.extend(amount("amount")?.to_le_bytes());
+                    authority(&["mint", "account"])
+                }
+                "mintToChecked" => {
+                    let (amount, decimals) = token_amount()?;
+                    data.push(14);
+                    data.extend(amount.to_le_bytes());
+                    data.push(decimals);
+                    authority(&["mint", "account"])
+                }
+                "burn" => {
+                    data.push(8);
+                    data.extend(amount("amount")?.to_le_bytes());
+                    authority(&["account", "mint"])
+                }
+                "burnChecked" => {
+                    let (amount, decimals) = token_amount()?;
+                    data.push(15);
+                    data.extend(amount.to_le_bytes());
+                    data.push(decimals);
+                    authority(&["account", "mint"])
+                }
+                "closeAccount" => {
+                    data.push(9);
+                    authority(&["account", "destination"])
+                }
+                "syncNative" => {
+                    data.push(17);
+                    vec!["account"]
+                }
+                "initializeAccount" => {
+                    data.push(1);
+                    vec!["account", "mint", "owner", "rentSysvar"]
+                }
+                "initializeAccount2" | "initializeAccount3" => {
+                    data.push(if kind == "initializeAccount2" { 16 } else { 18 });
+                    data.extend(bs58::decode(info["owner"].as_str()?).into_vec().ok()?);
+                    vec!["account", "mint", "rentSysvar"]
+                }
+                "approve" => {
+                    data.push(4);
+                    data.extend(amount("amount")?.to_le_bytes());
+                    authority(&["source", "delegate"])
+                }
+                "approveChecked" => {
+                    let (amount, decimals) = token_amount()?;
+                    data.push(13);
+                    data.extend(amount.to_le_bytes());
+                    data.push(decimals);
+                    authority(&["source", "mint", "delegate"])
+                }
+                "revoke" => {
+                    data.push(5);
+                    authority(&["source"])
+                }
+                "setAuthority" => {
+                    let authority_type = ["mintTokens", "freezeAccount", "accountOwner", "closeAccount"]
+                        .iter()
+                        .position(|name| info["authorityType"] == *name)?;
+                    data.extend([6, authority_type as u8]);
+                    match info["newAuthority"].as_str() {
+                        Some(new_authority) => {
+                            data.push(1);
+                            data.extend(bs58::decode(new_authority).into_vec().ok()?);
+                        }
+                        None => data.push(0),
+                    }
+                    // Only one of mint and account is set.
+                    authority(&["mint", "account"])
+                }
+                "getAccountDataSize" => {
+                    // Extension types would follow as u16s; plain accounts have none.
+                    if info["extensionTypes"].as_array().is_some_and(|types| !types.is_empty()) {
+                        return None;
+                    }
+                    data.push(21);
+                    vec!["mint"]
+                }
+                "initializeImmutableOwner" => {
+                    data.push(22);
+                    vec!["account"]
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some((names, data))
+}
+
+/// A transaction in wire format: signatures, then a legacy or v0 message.
+fn wire_transaction(bytes: &[u8]) -> Result<Transaction> {
+    let mut reader = Reader { bytes, position: 0 };
+    let signatures = (0..reader.compact_u16()?).map(|_| reader.take(64).map(<[u8]>::to_vec)).collect::<Result<_>>()?;
+
+    let versioned = reader.peek()? & 0x80 != 0;
+    if versioned {
+        let version = reader.byte()? & 0x7f;
+        if version != 0 {
+            return Err(format!("unsupported message version {}", version));
+        }
+    }
+    let header = MessageHeader {
+        num_required_signatures: reader.byte()? as u32,
+        num_readonly_signed_accounts: reader.byte()? as u32,
+        num_readonly_unsigned_accounts: reader.byte()? as u32,
+    };
+    let account_keys = (0..reader.compact_u16()?).map(|_| reader.take(32).map(<[u8]>::to_vec)).collect::<Result<_>>()?;
+    let recent_blockhash = reader.take(32)?.to_vec();
+    let instructions = (0..reader.compact_u16()?)
+        .map(|_| {
+            Ok(CompiledInstruction {
+                program_id_index: reader.byte()? as u32,
+                accounts: reader.bytes()?,
+                data: reader.bytes()?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let address_table_lookups = match versioned {
+        true => (0..reader.compact_u16()?)
+            .map(|_| {
+                Ok(MessageAddressTableLookup {
+                    account_key: reader.take(32)?.to_vec(),
+                    writable_indexes: reader.bytes()?,
+                    readonly_indexes: reader.bytes()?,
+                })
+            })
+            .collect::<Result<_>>()?,
+        false => Vec::new(),
+    };
+
+    Ok(Transaction {
+        signatures,
+        message: Some(Message {
+            header: Some(header),
+            account_keys,
+            recent_blockhash,
+            instructions,
+            versioned,
+            address_table_lookups,
+        }),
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.position + len;
+        let slice = self.bytes.get(self.position..end).ok_or("transaction is truncated")?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.bytes.get(self.position).copied().ok_or_else(|| "transaction is truncated".to_string())
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Length-prefixed bytes.
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.compact_u16()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Solana's "shortvec" length: 7 bits per byte, low bits first.
+    fn compact_u16(&mut self) -> Result<usize> {
+        let mut value = 0usize;
+        for shift in [0, 7, 14] {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid compact-u16 length".to_string())
+    }
+}
+
+fn token_balances(balances: &Value) -> Vec<TokenBalance> {
+    balances
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|balance| {
+            let amount = &balance["uiTokenAmount"];
+            TokenBalance {
+                account_index: balance["accountIndex"].as_u64().unwrap_or(0) as u32,
+                mint: string(&balance["mint"]),
+                ui_token_amount: Some(UiTokenAmount {
+                    ui_amount: amount["uiAmount"].as_f64().unwrap_or(0.0),
+                    decimals: amount["decimals"].as_u64().unwrap_or(0) as u32,
+                    amount: string(&amount["amount"]),
+                    ui_amount_string: string(&amount["uiAmountString"]),
+                }),
+                owner: string(&balance["owner"]),
+                program_id: string(&balance["programId"]),
+            }
+        })
+        .collect()
+}
+
+fn rewards(rewards: &Value) -> Vec<Reward> {
+    rewards
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|reward| Reward {
+            pubkey: string(&reward["pubkey"]),
+            lamports: reward["lamports"].as_i64().unwrap_or(0),
+            post_balance: reward["postBalance"].as_u64().unwrap_or(0),
+            reward_type: match reward["rewardType"].as_str() {
+                Some("Fee") => RewardType::Fee,
+                Some("Rent") => RewardType::Rent,
+                Some("Staking") => RewardType::Staking,
+                Some("Voting") => RewardType::Voting,
+                _ => RewardType::Unspecified,
+            } as i32,
+            commission: reward["commission"].as_u64().map(|c| c.to_string()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+fn u64_list(values: &Value) -> Vec<u64> {
+    values.as_array().map(|values| values.iter().filter_map(Value::as_u64).collect()).unwrap_or_default()
+}
+
+fn base58(value: &Value) -> Result<Vec<u8>> {
+    let text = value.as_str().ok_or_else(|| format!("expected a base58 string, got {}", value))?;
+    bs58::decode(text).into_vec().map_err(|err| format!("'{}': {}", text, err))
+}
+
+fn base58_list(values: &Value) -> Result<Vec<Vec<u8>>> {
+    values.as_array().map(Vec::as_slice).unwrap_or_default().iter().map(base58).collect()
+}
+
+/// Standard base64 with optional padding.
+fn base64(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("invalid base64 character '{}'", c as char)),
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    #[test]
+    fn reads_v0_wire_transactions() {
+        // One signature; header 1/0/1; keys: payer and a program; one instruction calling
+        // the program with account 0 and data [7]; one lookup table.
+        let mut wire = vec![1];
+        wire.extend([9u8; 64]);
+        wire.extend([0x80, 1, 0, 1, 2]);
+        wire.extend([1u8; 32]);
+        wire.extend([2u8; 32]);
+        wire.extend([3u8; 32]);
+        wire.extend([1, 1, 1, 0, 1, 7]);
+        wire.extend([1]);
+        wire.extend([4u8; 32]);
+        wire.extend([1, 5, 2, 6, 7]);
+
+        let transaction = wire_transaction(&wire).unwrap();
+        let message = transaction.message.unwrap();
+        assert!(message.versioned);
+        assert_eq!(message.account_keys, vec![vec![1u8; 32], vec![2u8; 32]]);
+        assert_eq!(message.instructions[0].accounts, vec![0]);
+        assert_eq!(message.instructions[0].data, vec![7]);
+        assert_eq!(message.address_table_lookups[0].writable_indexes, vec![5]);
+        assert_eq!(message.address_table_lookups[0].readonly_indexes, vec![6, 7]);
+        assert_eq!(base64("AAEC/w==").unwrap(), vec![0, 1, 2, 255]);
+    }
+
+    #[test]
+    fn encodes_parsed_token_transfers() {
+        let keys = ["source1111111111111111111111111111111111111", "dest11111111111111111111111111111111111111", "owner1111111111111111111111111111111111111"];
+        let indexes: HashMap<String, u8> =
+            keys.iter().chain([&TOKEN]).enumerate().map(|(i, key)| (key.to_string(), i as u8)).collect();
+        let parsed = json!({
+            "program": "spl-token",
+            "programId": TOKEN,
+            "parsed": {
+                "type": "transferChecked",
+                "info": {
+                    "source": keys[0],
+                    "mint": keys[1],
+                    "destination": keys[1],
+                    "authority": keys[2],
+                    "tokenAmount": { "amount": "1500", "decimals": 6 }
+                }
+            },
+            "stackHeight": 2
+        });
+
+        let compiled = instruction(&parsed, &indexes).unwrap();
+        assert_eq!(compiled.program_id_index, 3);
+        assert_eq!(compiled.accounts, vec![0, 1, 1, 2]);
+        let mut data = vec![12];
+        data.extend(1500u64.to_le_bytes());
+        data.push(6);
+        assert_eq!(compiled.data, data);
+    }
+
+    #[test]
+    fn encodes_parsed_account_setup() {
+        let owner = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+        let owner_bytes = bs58::decode(owner).into_vec().unwrap();
+        let encode =
+            |program: &str, kind: &str, info: Value| encode_parsed(program, &json!({ "type": kind, "info": info }));
+
+        let ata =
+            json!({ "source": "p", "account": "a", "wallet": "w", "mint": "m", "systemProgram": "s", "tokenProgram": "t" });
+        let (names, data) = encode("spl-associated-token-account", "create", ata.clone()).unwrap();
+        assert_eq!((names.len(), data), (7, vec![]));
+        assert_eq!(encode("spl-associated-token-account", "createIdempotent", ata).unwrap().1, vec![1]);
+
+        let initialize = json!({ "account": "a", "mint": "m", "owner": owner });
+        let (names, data) = encode("spl-token", "initializeAccount3", initialize).unwrap();
+        assert_eq!(names[..2], ["account", "mint"]);
+        assert_eq!(data, [vec![18], owner_bytes.clone()].concat());
+
+        let approve = json!({ "source": "s", "delegate": "d", "owner": "o", "amount": "7" });
+        let (_, data) = encode("spl-token", "approve", approve).unwrap();
+        assert_eq!(data, [vec![4], 7u64.to_le_bytes().to_vec()].concat());
+
+        let set_authority =
+            json!({ "account": "a", "authorityType": "closeAccount", "newAuthority": owner, "authority": "o" });
+        let (_, data) = encode("spl-token", "setAuthority", set_authority).unwrap();
+        assert_eq!(data, [vec![6, 3, 1], owner_bytes].concat());
+
+        assert_eq!(encode_parsed("spl-memo", &json!("gm")).unwrap(), (vec![], b"gm".to_vec()));
+        let data_size = json!({ "mint": "m", "extensionTypes": ["memoTransfer"] });
+        assert!(encode("spl-token", "getAccountDataSize", data_size).is_none());
+    }
+
+    #[test]
+    fn rejects_unsupported_parsed_instructions() {
+        let vote = "Vote111111111111111111111111111111111111111";
+        let indexes = HashMap::from([(vote.to_string(), 0u8)]);
+        let parsed = json!({
+            "program": "vote",
+            "programId": vote,
+            "parsed": { "type": "towersync", "info": {} },
+            "stackHeight": null
+        });
+
+        let err = instruction(&parsed, &indexes).unwrap_err();
+        assert!(err.contains("\"towersync\"") && err.contains("json or base64"), "{}", err);
+    }
+}
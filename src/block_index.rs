@@ -0,0 +1,99 @@
+use std::collections::BTreeSet;
+
+use crate::instructions::{is_vote, ordered_instructions};
+use crate::pb::sf::substreams::index::v1::Keys;
+use substreams::errors::Error;
+use substreams_solana::base58;
+use substreams_solana::pb::sf::solana::r#type::v1::Block;
+
+/// Block index keys: `program:<id>` for every program a non-vote transaction invokes,
+/// top-level or CPI, and `mint:<mint>` for every mint in its token balances. Failed
+/// transactions are indexed too, so the keys are a superset of what any module reads.
+/// Modules declare a `blockFilter` on these keys and skip the blocks that do not match.
+#[substreams::handlers::map]
+pub fn index_programs(block: Block) -> Result<Keys, Error> {
+    // Sorted and deduplicated, so the same block always yields the same keys.
+    let mut keys = BTreeSet::new();
+
+    for trx in &block.transactions {
+        let (meta, message) = match (&trx.meta, trx.transaction.as_ref().and_then(|t| t.message.as_ref())) {
+            (Some(meta), Some(message)) => (meta, message),
+            _ => continue,
+        };
+
+        if is_vote(message, meta) {
+            continue;
+        }
+
+        for inst in &ordered_instructions(message, meta) {
+            keys.insert(format!("program:{}", base58::encode(inst.program_id)));
+        }
+        for balance in meta.pre_token_balances.iter().chain(meta.post_token_balances.iter()) {
+            keys.insert(format!("mint:{}", balance.mint));
+        }
+    }
+
+    Ok(Keys { keys: keys.into_iter().collect() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{JUPITER_PROGRAM_IDS, TOKEN_PROGRAM_ID, VOTE_PROGRAM_ID};
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message, TokenBalance,
+        Transaction, TransactionStatusMeta,
+    };
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn transaction(program: &str, inner: Option<&str>, mint: Option<&str>) -> ConfirmedTransaction {
+        let mut account_keys = vec![vec![1u8; 32], base58::decode(program).unwrap()];
+        account_keys.extend(inner.map(|inner| base58::decode(inner).unwrap()));
+        ConfirmedTransaction {
+            transaction: Some(Transaction {
+                signatures: vec![vec![9u8; 64]],
+                message: Some(Message {
+                    account_keys,
+                    instructions: vec![CompiledInstruction { program_id_index: 1, ..Default::default() }],
+                    ..Default::default()
+                }),
+            }),
+            meta: Some(TransactionStatusMeta {
+                inner_instructions: inner
+                    .map(|_| InnerInstructions {
+                        index: 0,
+                        instructions: vec![InnerInstruction { program_id_index: 2, ..Default::default() }],
+                    })
+                    .into_iter()
+                    .collect(),
+                post_token_balances: mint
+                    .map(|mint| TokenBalance { mint: mint.to_string(), ..Default::default() })
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn indexes_programs_and_mints_of_non_vote_transactions() {
+        let block = Block {
+            transactions: vec![
+                transaction(VOTE_PROGRAM_ID, None, None),
+                transaction(JUPITER_PROGRAM_IDS[0], Some(TOKEN_PROGRAM_ID), Some(USDC)),
+            ],
+            ..Default::default()
+        };
+
+        let keys = __impl_index_programs(block).unwrap().keys;
+        assert_eq!(
+            keys,
+            vec![
+                format!("mint:{}", USDC),
+                format!("program:{}", JUPITER_PROGRAM_IDS[0]),
+                format!("program:{}", TOKEN_PROGRAM_ID),
+            ]
+        );
+    }
+}
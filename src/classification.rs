@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+
+use crate::constants::{
+    JUPITER_V2_PROGRAM_ID, JUPITER_V3_PROGRAM_ID, JUPITER_V4_PROGRAM_ID, JUPITER_V6_PROGRAM_ID, LIMIT_ORDER_PROGRAM_ID,
+};
+use crate::instructions::{ordered_instructions, tx_record_id, InstructionRef, TxInstruction};
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{TransactionClassification, TransactionClassifications};
+use crate::program_keys::program_key;
+use chrono::DateTime;
+use substreams::errors::Error;
+use substreams_solana::base58;
+
+// --- PROGRAM IDS ---
+
+// Jupiter aggregator and limit order ids live in constants.rs.
+
+// OTHER DEX / SWAPS (Direct interactions)
+const PHOENIX_PROGRAM: &str = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY";
+const RAYDIUM_V4_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+const ORCA_WHIRLPOOL_PROGRAM: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+
+// NFT MARKETS
+const MAGIC_EDEN_V2_PROGRAM: &str = "M2mx93ekt1fmXSVkTrUL9xVFHkmME8HTUi5Cyc5aF7K";
+const TENSOR_SWAP_PROGRAM: &str = "TSWAPaqyCSx2KABk68Shruf4rp7CxcNi8hAsbdwmHbN";
+
+// LENDING / BORROWING
+const KAMINO_LENDING_PROGRAM: &str = "KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD";
+const MARGINFI_V2_PROGRAM: &str = "MFv2hWf31Z9kbCa1snEPYctwafyhdvnV7FZnsebVac6";
+const SOLEND_PROGRAM: &str = "So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3ZUE8uB5y";
+
+// STAKING (Liquid & Native)
+const STAKE_PROGRAM: &str = "Stake11111111111111111111111111111111111111";
+const MARINADE_PROGRAM: &str = "MarBmsSgKXdrN1egZf5sqe1CJNPUbNEXRPn4nsPAafF";
+const JITO_STAKE_PROGRAM: &str = "Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb";
+
+// PERPETUALS
+const DRIFT_V2_PROGRAM: &str = "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH";
+
+const DEFAULT_LABELS: [(&str, &str, Category); 17] = [
+    // Jupiter Aggregator
+    (JUPITER_V6_PROGRAM_ID, "SWAP_JUPITER", Category::Swap),
+    (JUPITER_V4_PROGRAM_ID, "SWAP_JUPITER", Category::Swap),
+    (JUPITER_V3_PROGRAM_ID, "SWAP_JUPITER", Category::Swap),
+    (JUPITER_V2_PROGRAM_ID, "SWAP_JUPITER", Category::Swap),
+    (LIMIT_ORDER_PROGRAM_ID, "SWAP_JUPITER", Category::Swap),
+    // Direct DEX Swaps
+    (PHOENIX_PROGRAM, "SWAP_PHOENIX", Category::Swap),
+    (RAYDIUM_V4_PROGRAM, "SWAP_RAYDIUM", Category::Swap),
+    (ORCA_WHIRLPOOL_PROGRAM, "SWAP_ORCA", Category::Swap),
+    // NFT
+    (MAGIC_EDEN_V2_PROGRAM, "NFT_TRADE_MAGIC_EDEN", Category::NftTrade),
+    (TENSOR_SWAP_PROGRAM, "NFT_TRADE_TENSOR", Category::NftTrade),
+    // Lending
+    (KAMINO_LENDING_PROGRAM, "LEND_KAMINO", Category::Lend),
+    (MARGINFI_V2_PROGRAM, "LEND_MARGINFI", Category::Lend),
+    (SOLEND_PROGRAM, "LEND_SOLEND", Category::Lend),
+    // Staking
+    (STAKE_PROGRAM, "STAKE_NATIVE", Category::Stake),
+    (MARINADE_PROGRAM, "STAKE_MARINADE", Category::Stake),
+    (JITO_STAKE_PROGRAM, "STAKE_JITO", Category::Stake),
+    // Perps
+    (DRIFT_V2_PROGRAM, "PERP_DRIFT", Category::Perp),
+];
+
+/// Raw keys of `DEFAULT_LABELS`, in the same order; a malformed address fails the build.
+const DEFAULT_LABEL_KEYS: [[u8; 32]; DEFAULT_LABELS.len()] = default_label_keys();
+
+const fn default_label_keys() -> [[u8; 32]; DEFAULT_LABELS.len()] {
+    let mut keys = [[0u8; 32]; DEFAULT_LABELS.len()];
+    let mut i = 0;
+    while i < keys.len() {
+        keys[i] = program_key(DEFAULT_LABELS[i].0);
+        i += 1;
+    }
+    keys
+}
+
+const DEFAULT_PRIORITIES: [(&str, u8); 5] = [
+    // NFT Trades are specific; usually override swaps
+    ("NFT", 5),
+    // Lending/Perps often use swaps internally, so we prioritize them
+    // if the Lending Program is the top-level invoker.
+    ("LEND", 4),
+    ("PERP", 4),
+    ("SWAP", 3),
+    ("STAKE", 2),
+];
+
+// Priority of a label no rule matches.
+const FALLBACK_PRIORITY: u8 = 1;
+
+/// What a classified program does. Labels are free-form and params can rename them, so
+/// modules that need swap legs select on the category rather than the label text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Category {
+    Swap,
+    NftTrade,
+    Lend,
+    Stake,
+    Perp,
+}
+
+impl Category {
+    const ALL: [Category; 5] = [Category::Swap, Category::NftTrade, Category::Lend, Category::Stake, Category::Perp];
+
+    /// Name written to `BalanceChange.category`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Swap => "SWAP",
+            Category::NftTrade => "NFT_TRADE",
+            Category::Lend => "LEND",
+            Category::Stake => "STAKE",
+            Category::Perp => "PERP",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, Error> {
+        Category::ALL.into_iter().find(|category| category.as_str() == value).ok_or_else(|| {
+            let names: Vec<&str> = Category::ALL.into_iter().map(Category::as_str).collect();
+            anyhow::anyhow!("invalid category '{}': expected one of {}", value, names.join(", "))
+        })
+    }
+}
+
+/// Program → label and category mapping, and label priorities, used to classify
+/// transactions and instructions. Starts from the built-in defaults; params add or
+/// override rules. Programs are keyed by raw key so instructions are classified without
+/// encoding their program ids.
+pub(crate) struct Classifier {
+    labels: HashMap<[u8; 32], String>,
+    categories: HashMap<[u8; 32], Category>,
+    priorities: Vec<(String, u8)>,
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Classifier {
+            labels: DEFAULT_LABEL_KEYS
+                .into_iter()
+                .zip(DEFAULT_LABELS)
+                .map(|(key, (_, label, _))| (key, label.to_string()))
+                .collect(),
+            categories: DEFAULT_LABEL_KEYS
+                .into_iter()
+                .zip(DEFAULT_LABELS)
+                .map(|(key, (_, _, category))| (key, category))
+                .collect(),
+            priorities: DEFAULT_PRIORITIES.iter().map(|(prefix, priority)| (prefix.to_string(), *priority)).collect(),
+        }
+    }
+}
+
+impl Classifier {
+    /// Builds a classifier from `&`-separated `label.*`, `category.*` and `priority.*` rules.
+    pub fn from_params(params: &str) -> Result<Self, Error> {
+        let mut classifier = Classifier::default();
+
+        for entry in params.split('&').map(str::trim).filter(|s| !s.is_empty()) {
+            let applied = match entry.split_once('=') {
+                Some((key, value)) => classifier.apply(key, value)?,
+                None => false,
+            };
+            if !applied {
+                return Err(anyhow::anyhow!("unknown classification rule '{}'", entry));
+            }
+        }
+
+        Ok(classifier)
+    }
+
+    /// Raw key of every program with a label, built-in or from params.
+    pub fn programs(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.labels.keys()
+    }
+
+    /// Label and priority of a raw program key, if it is classified.
+    pub fn classify(&self, program_id: &[u8]) -> Option<(&str, u8)> {
+        let label = self.labels.get(<&[u8; 32]>::try_from(program_id).ok()?)?;
+        Some((label, self.priority(label)))
+    }
+
+    /// Category of a labelled program; programs labelled from params have none unless a
+    /// `category.*` rule gives them one.
+    pub fn category(&self, program_id: &[u8]) -> Option<Category> {
+        let key = <&[u8; 32]>::try_from(program_id).ok()?;
+        self.labels.get(key)?;
+        self.categories.get(key).copied()
+    }
+
+    /// Priority of the longest rule prefix matching the label.
+    pub fn priority(&self, label: &str) -> u8 {
+        self.priorities
+            .iter()
+            .filter(|(prefix, _)| label.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, priority)| *priority)
+            .unwrap_or(FALLBACK_PRIORITY)
+    }
+
+    /// Applies one `key=value` rule from params. Returns `Ok(false)` when the key is not
+    /// a classification rule:
+    /// - `label.<program_id>=<LABEL>` maps a program to a label; an empty label removes it.
+    /// - `category.<program_id>=<CATEGORY>` sets what the program does (`SWAP`, `NFT_TRADE`,
+    ///   `LEND`, `STAKE` or `PERP`); an empty value removes it. Relabelling keeps the category.
+    /// - `priority.<LABEL_PREFIX>=<0-255>` sets the priority of labels with that prefix;
+    ///   the longest matching prefix wins, and priority 0 never overrides `SEND`.
+    pub fn apply(&mut self, key: &str, value: &str) -> Result<bool, Error> {
+        if let Some(program_id) = key.strip_prefix("label.") {
+            let program = parse_program_id(program_id)?;
+            if value.is_empty() {
+                self.labels.remove(&program);
+            } else {
+                validate_label(value)?;
+                self.labels.insert(program, value.to_string());
+            }
+            return Ok(true);
+        }
+
+        if let Some(program_id) = key.strip_prefix("category.") {
+            let program = parse_program_id(program_id)?;
+            if value.is_empty() {
+                self.categories.remove(&program);
+            } else {
+                self.categories.insert(program, Category::parse(value)?);
+            }
+            return Ok(true);
+        }
+
+        if let Some(prefix) = key.strip_prefix("priority.") {
+            validate_label(prefix)?;
+            let priority: u8 = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid priority '{}' for '{}': expected 0-255", value, prefix))?;
+            match self.priorities.iter_mut().find(|(existing, _)| existing == prefix) {
+                Some((_, existing)) => *existing = priority,
+                None => self.priorities.push((prefix.to_string(), priority)),
+            }
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+}
+
+fn parse_program_id(program_id: &str) -> Result<[u8; 32], Error> {
+    base58::decode(program_id)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("invalid program id '{}': expected a base58 32-byte address", program_id))
+}
+
+fn validate_label(label: &str) -> Result<(), Error> {
+    let valid = !label.is_empty()
+        && label.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    match valid {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("invalid label '{}': expected A-Z, 0-9 and '_'", label)),
+    }
+}
+
+// Separates labels in a CPI path, outermost first.
+const CPI_PATH_SEPARATOR: &str = ">";
+
+/// Every label detected in one transaction, and where it was detected.
+pub(crate) struct TxClassification<'a> {
+    /// Highest-priority label across all instructions, top-level first; `SEND` when
+    /// no program is classified.
+    pub change_type: String,
+    /// Category of the program behind `change_type`.
+    pub category: Option<Category>,
+    /// Every distinct label, in execution order.
+    pub labels: Vec<&'a str>,
+    /// Highest-priority label among the top-level programs themselves.
+    pub invoker_label: &'a str,
+    /// Highest-priority label per top-level instruction, including its CPIs, with its
+    /// program's category.
+    instruction_labels: HashMap<i32, (&'a str, u8, Option<Category>)>,
+    /// Highest-priority label on the CPI path down to each inner instruction, for
+    /// inner instructions with a labelled path.
+    inner_labels: HashMap<InstructionRef, (&'a str, u8, Option<Category>)>,
+    /// Category of the innermost classified program on the CPI path down to each
+    /// instruction, for instructions with a labelled path.
+    categories: HashMap<InstructionRef, Option<Category>>,
+    /// Label of each top-level program.
+    invokers: HashMap<i32, &'a str>,
+    /// Labels from the top-level instruction down to each instruction.
+    paths: HashMap<InstructionRef, Vec<&'a str>>,
+}
+
+impl<'a> TxClassification<'a> {
+    pub fn new(classifier: &'a Classifier, instructions: &[TxInstruction]) -> Self {
+        let mut change_type = "SEND".to_string();
+        let mut category = None;
+        let mut highest_priority = 0;
+        let mut invoker_label = "";
+        let mut invoker_priority = 0;
+        let mut labels: Vec<&str> = Vec::new();
+        let mut instruction_labels: HashMap<i32, (&str, u8, Option<Category>)> = HashMap::new();
+        let mut inner_labels = HashMap::new();
+        let mut categories = HashMap::new();
+        let mut invokers = HashMap::new();
+        let mut paths = HashMap::new();
+
+        // A. Top-Level Instructions, then B. Inner Instructions (CPI)
+        let top_level = instructions.iter().filter(|inst| inst.at.inner_index < 0);
+        let inner = instructions.iter().filter(|inst| inst.at.inner_index >= 0);
+        for inst in top_level.chain(inner) {
+            if let Some((label, priority)) = classifier.classify(inst.program_id) {
+                if priority > highest_priority {
+                    highest_priority = priority;
+                    change_type = label.to_string();
+                    category = classifier.category(inst.program_id);
+                }
+            }
+        }
+
+        // C. Per top-level instruction, the same rule scoped to the instruction and its
+        // CPIs, plus the path of labels leading to every instruction.
+        let mut stack: Vec<Option<(&str, u8, Option<Category>)>> = Vec::new();
+        for inst in instructions {
+            let classified = classifier.classify(inst.program_id);
+            let program_category = classifier.category(inst.program_id);
+
+            if let Some((label, priority)) = classified {
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+                let entry = instruction_labels.entry(inst.at.index).or_insert((label, priority, program_category));
+                if priority > entry.1 {
+                    *entry = (label, priority, program_category);
+                }
+                if inst.at.inner_index < 0 {
+                    invokers.insert(inst.at.index, label);
+                    if priority > invoker_priority {
+                        invoker_priority = priority;
+                        invoker_label = label;
+                    }
+                }
+            }
+
+            stack.truncate(inst.stack_height.saturating_sub(1) as usize);
+            stack.push(classified.map(|(label, priority)| (label, priority, program_category)));
+            paths.insert(inst.at, stack.iter().flatten().map(|(label, _, _)| *label).collect());
+            if let Some((_, _, category)) = stack.iter().rev().flatten().next() {
+                categories.insert(inst.at, *category);
+            }
+            if inst.at.inner_index >= 0 {
+                // The first of equal priorities wins, as for the top-level labels.
+                let best = stack.iter().flatten().copied().reduce(|best, next| if next.1 > best.1 { next } else { best });
+                if let Some(best) = best {
+                    inner_labels.insert(inst.at, best);
+                }
+            }
+        }
+
+        TxClassification {
+            change_type,
+            category,
+            labels,
+            invoker_label,
+            instruction_labels,
+            inner_labels,
+            categories,
+            invokers,
+            paths,
+        }
+    }
+
+    /// Label of a top-level instruction including its CPIs, or of the highest-priority
+    /// program on the CPI path down to an inner instruction, falling back to its
+    /// top-level instruction's; unattributed changes get the transaction's.
+    pub fn label(&self, at: InstructionRef) -> String {
+        if at == InstructionRef::NONE {
+            return self.change_type.clone();
+        }
+        self.labelled(at).map(|(label, _, _)| label.to_string()).unwrap_or_else(|| "SEND".to_string())
+    }
+
+    pub fn priority(&self, at: InstructionRef) -> u8 {
+        self.labelled(at).map(|(_, priority, _)| *priority).unwrap_or(0)
+    }
+
+    /// Category of the classified program closest to `at` on its CPI path, so the legs of
+    /// a Jupiter swap run inside a lending instruction stay `SWAP` even though the higher
+    /// priority lending label names them. Falls back to the category behind `label(at)`;
+    /// `None` for `SEND`.
+    pub fn category(&self, at: InstructionRef) -> Option<Category> {
+        if at == InstructionRef::NONE {
+            return self.category;
+        }
+        match self.categories.get(&at) {
+            Some(category) => *category,
+            None => self.labelled(at).and_then(|(_, _, category)| *category),
+        }
+    }
+
+    fn labelled(&self, at: InstructionRef) -> Option<&(&'a str, u8, Option<Category>)> {
+        self.inner_labels.get(&at).or_else(|| self.instruction_labels.get(&at.index))
+    }
+
+    /// Label of the top-level program; unattributed changes get the transaction's.
+    pub fn invoker(&self, at: InstructionRef) -> &'a str {
+        if at == InstructionRef::NONE {
+            return self.invoker_label;
+        }
+        self.invokers.get(&at.index).copied().unwrap_or("")
+    }
+
+    /// Labels from the top-level instruction down to `at`, e.g. `LEND_KAMINO>SWAP_JUPITER`.
+    pub fn cpi_path(&self, at: InstructionRef) -> String {
+        self.paths.get(&at).map(|path| path.join(CPI_PATH_SEPARATOR)).unwrap_or_default()
+    }
+
+    /// Every distinct labelled CPI path, leaving out paths another one extends.
+    pub fn cpi_paths(&self, instructions: &[TxInstruction]) -> Vec<String> {
+        let mut all: Vec<String> = Vec::new();
+        for inst in instructions {
+            let path = self.cpi_path(inst.at);
+            if !path.is_empty() && !all.contains(&path) {
+                all.push(path);
+            }
+        }
+        all.iter()
+            .filter(|path| {
+                let prefix = format!("{}{}", path, CPI_PATH_SEPARATOR);
+                !all.iter().any(|other| other.starts_with(&prefix))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[substreams::handlers::map]
+pub fn map_transaction_classifications(params: String, block: FilteredBlock) -> Result<TransactionClassifications, Error> {
+    let classifier = Classifier::from_params(&params)?;
+    let timestamp = block.block_time.as_ref().map(|t| t.timestamp).unwrap_or(0);
+    let block_date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%d").to_string();
+    let mut transactions = Vec::new();
+
+    for (tx_index, trx) in block.indexed_transactions() {
+        let (meta, transaction) = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) if meta.err.is_none() => (meta, transaction),
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let instructions = ordered_instructions(message, meta);
+        let classification = TxClassification::new(&classifier, &instructions);
+
+        transactions.push(TransactionClassification {
+            block_date: block_date.clone(),
+            block_time: timestamp as u64,
+            block_slot: block.slot,
+            tx_id,
+            change_type: classification.change_type.clone(),
+            labels: classification.labels.iter().map(|label| label.to_string()).collect(),
+            invoker_label: classification.invoker_label.to_string(),
+            cpi_paths: classification.cpi_paths(&instructions),
+            tx_index,
+            id: tx_record_id(block.slot, tx_index),
+        });
+    }
+
+    Ok(TransactionClassifications { transactions })
+}
@@ -1,10 +1,35 @@
 pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+pub const JUPITER_V6_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+pub const JUPITER_V4_PROGRAM_ID: &str = "JUP4Fb2cqiRUcaTHdrPC8h2gNsA2ETXiPDD33WcGuJB";
+pub const JUPITER_V3_PROGRAM_ID: &str = "JUP3c2Uh3WA4Ng34tw6kPd2G4C5BB21Xo36Je1s32Ph";
+pub const JUPITER_V2_PROGRAM_ID: &str = "JUP2jxvXaqu7NQY1GmNF4m1vodw12LVXYxbFL2uJvfo";
 
 pub const JUPITER_PROGRAM_IDS: [&str; 5] = [
-    "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
-    "JUP4Fb2cqiRUcaTHdrPC8h2gNsA2ETXiPDD33WcGuJB",
-    "JUP3c2Uh3WA4Ng34tw6kPd2G4C5BB21Xo36Je1s32Ph",
-    "JUP2jxvXaqu7NQY1GmNF4m1vodw12LVXYxbFL2uJvfo",
-    "JUPoNjAxXgZ4rjzxzPMP4oxduvQsQtZzyknqvzYNrNu",
+    JUPITER_V6_PROGRAM_ID,
+    JUPITER_V4_PROGRAM_ID,
+    JUPITER_V3_PROGRAM_ID,
+    JUPITER_V2_PROGRAM_ID,
+    LIMIT_ORDER_PROGRAM_ID,
+];
+
+/// Version names of `JUPITER_PROGRAM_IDS`, in the same order.
+pub const JUPITER_PROGRAM_VERSIONS: [&str; 5] = ["v6", "v4", "v3", "v2", "limit"];
+
+pub const VOTE_PROGRAM_ID: &str = "Vote111111111111111111111111111111111111111";
+
+pub const WRAPPED_SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// USD stablecoins priced at 1.0 when valuing swap legs.
+pub const USD_STABLE_MINTS: [&str; 2] = [
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", // USDC
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", // USDT
 ];
 
+pub const LIMIT_ORDER_PROGRAM_ID: &str = "jupoNjAxXgZ4rjzxzPMP4oxduvQsQtZzyknqvzYNrNu";
+pub const DCA_PROGRAM_ID: &str = "DCA265Vj8a9CEuX1eb1LWRnDT7uK6q1xMipnNyatn23M";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
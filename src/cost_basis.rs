@@ -0,0 +1,388 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::classification::Category;
+use crate::constants::USD_STABLE_MINTS;
+use crate::pb::sf::jupiter::v1::{
+    BalanceChange, BalanceChanges, CostBasisLeg, CostBasisLegs, RealizedPnl, RealizedPnls,
+};
+use substreams::errors::Error;
+use substreams::store::{
+    StoreAdd, StoreAddFloat64, StoreDelete, StoreGet, StoreGetFloat64, StoreGetString, StoreMin, StoreMinFloat64,
+    StoreNew, StoreSet, StoreSetString,
+};
+
+/// A swap-classified balance change together with its position in the block output.
+pub(crate) struct SwapLeg<'a> {
+    pub ordinal: u64,
+    pub change: &'a BalanceChange,
+    pub amount: f64,
+}
+
+/// Groups balance changes of swap-category instructions by (tx_id, owner), preserving block order.
+/// The ordinal of each leg is its index in `changes.params`, so every module derived
+/// from the same `BalanceChanges` agrees on it.
+pub(crate) fn swap_legs_by_owner(changes: &BalanceChanges) -> Vec<Vec<SwapLeg<'_>>> {
+    let mut groups: Vec<Vec<SwapLeg>> = Vec::new();
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for (ordinal, change) in changes.params.iter().enumerate() {
+        if change.category != Category::Swap.as_str() {
+            continue;
+        }
+        let amount: f64 = match change.change_amount.parse() {
+            Ok(amount) => amount,
+            Err(_) => continue,
+        };
+
+        let slot = *index
+            .entry((change.tx_id.as_str(), change.owner.as_str()))
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        groups[slot].push(SwapLeg {
+            ordinal: ordinal as u64,
+            change,
+            amount,
+        });
+    }
+
+    groups
+}
+
+pub(crate) fn is_usd_stable(mint: &str) -> bool {
+    USD_STABLE_MINTS.contains(&mint)
+}
+
+#[substreams::handlers::map]
+pub fn map_cost_basis_legs(changes: BalanceChanges, prices: StoreGetFloat64) -> Result<CostBasisLegs, Error> {
+    let mut legs = Vec::new();
+
+    for group in swap_legs_by_owner(&changes) {
+        for leg in group {
+            let price = if is_usd_stable(&leg.change.mint) {
+                Some(1.0)
+            } else {
+                prices.get_at(leg.ordinal, format!("usd:{}", leg.change.mint))
+            };
+
+            legs.push(CostBasisLeg {
+                block_date: leg.change.block_date.clone(),
+                block_time: leg.change.block_time,
+                block_slot: leg.change.block_slot,
+                tx_id: leg.change.tx_id.clone(),
+                owner: leg.change.owner.clone(),
+                mint: leg.change.mint.clone(),
+                amount: leg.amount,
+                usd_value: price.map(|p| p * leg.amount.abs()).unwrap_or(0.0),
+                priced: price.is_some(),
+                ordinal: leg.ordinal,
+                tx_index: leg.change.tx_index,
+                instruction_index: leg.change.instruction_index,
+                inner_instruction_index: leg.change.inner_instruction_index,
+                stack_height: leg.change.stack_height,
+                id: leg.change.id.clone(),
+            });
+        }
+    }
+
+    Ok(CostBasisLegs { legs })
+}
+
+// Position state per (owner, mint). A store cannot read its own values, so the open
+// position is kept as the list of legs since it was last flat, replayed by
+// map_realized_pnl:
+// - store_cost_basis_totals adds every leg to "held:<owner>:<mint>" and counts it in
+//   "legs:<owner>:<mint>".
+// - store_cost_basis_lows keeps the lowest "held" value. Disposals of more than the
+//   swaps acquired are not matched, so the quantity still held is held - min(0, low).
+// - store_cost_basis_events writes leg n to "event:<owner>:<mint>:<n>" and, when the
+//   quantity held drops to zero, deletes the position's events and records the next
+//   leg in "event:<owner>:<mint>:start".
+
+// Fraction of a leg under which the remaining quantity counts as flat.
+const DUST: f64 = 1e-9;
+
+#[substreams::handlers::store]
+pub fn store_cost_basis_totals(legs: CostBasisLegs, store: StoreAddFloat64) {
+    for leg in legs.legs {
+        store.add(leg.ordinal, position_key("held", &leg.owner, &leg.mint), leg.amount);
+        store.add(leg.ordinal, position_key("legs", &leg.owner, &leg.mint), 1.0);
+    }
+}
+
+#[substreams::handlers::store]
+pub fn store_cost_basis_lows(legs: CostBasisLegs, totals: StoreGetFloat64, store: StoreMinFloat64) {
+    for leg in legs.legs {
+        let held = totals.get_at(leg.ordinal, position_key("held", &leg.owner, &leg.mint)).unwrap_or(0.0);
+        store.min(leg.ordinal, position_key("low", &leg.owner, &leg.mint), held);
+    }
+}
+
+#[substreams::handlers::store]
+pub fn store_cost_basis_events(legs: CostBasisLegs, totals: StoreGetFloat64, lows: StoreGetFloat64, store: StoreSetString) {
+    for leg in legs.legs {
+        let held = totals.get_at(leg.ordinal, position_key("held", &leg.owner, &leg.mint)).unwrap_or(0.0);
+        let low = lows.get_at(leg.ordinal, position_key("low", &leg.owner, &leg.mint)).unwrap_or(0.0);
+        let count = totals.get_at(leg.ordinal, position_key("legs", &leg.owner, &leg.mint)).unwrap_or(0.0) as u64;
+
+        if is_flat(held - low.min(0.0), leg.amount) {
+            store.delete_prefix(leg.ordinal as i64, &event_prefix(&leg.owner, &leg.mint));
+            store.set(leg.ordinal, format!("{}start", event_prefix(&leg.owner, &leg.mint)), &(count + 1).to_string());
+        } else {
+            store.set(leg.ordinal, format!("{}{}", event_prefix(&leg.owner, &leg.mint), count), &encode_event(&leg));
+        }
+    }
+}
+
+#[substreams::handlers::map]
+pub fn map_realized_pnl(
+    params: String,
+    legs: CostBasisLegs,
+    totals: StoreGetFloat64,
+    events: StoreGetString,
+) -> Result<RealizedPnls, Error> {
+    let method = CostBasisMethod::from_params(&params)?;
+    let mut positions: HashMap<(&str, &str), Position> = HashMap::new();
+    let mut items = Vec::new();
+
+    for leg in &legs.legs {
+        let position = positions
+            .entry((leg.owner.as_str(), leg.mint.as_str()))
+            .or_insert_with(|| Position::load(method, &totals, &events, &leg.owner, &leg.mint));
+
+        if leg.amount > 0.0 {
+            position.acquire(leg.amount, leg.priced.then_some(leg.usd_value));
+            continue;
+        }
+
+        let quantity = -leg.amount;
+        let (matched_amount, cost_basis_usd) = position.dispose(quantity);
+        if !leg.priced || matched_amount <= 0.0 {
+            continue;
+        }
+
+        let proceeds_usd = leg.usd_value * matched_amount / quantity;
+        items.push(RealizedPnl {
+            block_date: leg.block_date.clone(),
+            block_time: leg.block_time,
+            block_slot: leg.block_slot,
+            tx_id: leg.tx_id.clone(),
+            owner: leg.owner.clone(),
+            mint: leg.mint.clone(),
+            method: method.as_str().to_string(),
+            amount_disposed: quantity,
+            matched_amount,
+            proceeds_usd,
+            cost_basis_usd,
+            realized_pnl_usd: proceeds_usd - cost_basis_usd,
+            tx_index: leg.tx_index,
+            instruction_index: leg.instruction_index,
+            inner_instruction_index: leg.inner_instruction_index,
+            stack_height: leg.stack_height,
+            id: leg.id.clone(),
+        });
+    }
+
+    Ok(RealizedPnls { items })
+}
+
+#[derive(Clone, Copy)]
+enum CostBasisMethod {
+    Fifo,
+    Average,
+}
+
+impl CostBasisMethod {
+    /// Accepts `method=fifo` or `method=average`; an empty params string means FIFO.
+    fn from_params(params: &str) -> Result<Self, Error> {
+        let value = params.trim();
+        let value = value.strip_prefix("method=").unwrap_or(value);
+        match value.to_ascii_lowercase().as_str() {
+            "" | "fifo" => Ok(Self::Fifo),
+            "average" | "avg" => Ok(Self::Average),
+            other => Err(anyhow::anyhow!("unknown cost-basis method '{}', expected fifo or average", other)),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fifo => "FIFO",
+            Self::Average => "AVERAGE",
+        }
+    }
+}
+
+/// What one owner holds of one mint since the position was last flat. Only swap
+/// acquisitions count; the priced part carries the USD cost.
+struct Position {
+    method: CostBasisMethod,
+    /// FIFO lots, oldest first: quantity and unit cost when priced.
+    lots: VecDeque<(f64, Option<f64>)>,
+    quantity: f64,
+    /// Average method: priced quantity still held and its remaining USD cost.
+    priced_quantity: f64,
+    cost: f64,
+}
+
+impl Position {
+    fn new(method: CostBasisMethod) -> Self {
+        Position { method, lots: VecDeque::new(), quantity: 0.0, priced_quantity: 0.0, cost: 0.0 }
+    }
+
+    /// Replays the events stored for the position as of the start of the block.
+    fn load(method: CostBasisMethod, totals: &StoreGetFloat64, events: &StoreGetString, owner: &str, mint: &str) -> Self {
+        let mut position = Position::new(method);
+        let prefix = event_prefix(owner, mint);
+        let count = totals.get_first(position_key("legs", owner, mint)).unwrap_or(0.0) as u64;
+        let start = events.get_first(format!("{}start", prefix)).and_then(|start| start.parse().ok()).unwrap_or(1);
+
+        for n in start..=count {
+            if let Some(event) = events.get_first(format!("{}{}", prefix, n)) {
+                position.replay(&event);
+            }
+        }
+        position
+    }
+
+    fn replay(&mut self, event: &str) {
+        match event.strip_prefix('-') {
+            Some(quantity) => {
+                self.dispose(quantity.parse().unwrap_or(0.0));
+            }
+            None => {
+                let (quantity, cost) = event.split_once('@').unwrap_or((event, ""));
+                self.acquire(quantity.parse().unwrap_or(0.0), cost.parse().ok());
+            }
+        }
+    }
+
+    fn acquire(&mut self, quantity: f64, cost: Option<f64>) {
+        self.lots.push_back((quantity, cost.map(|cost| cost / quantity)));
+        self.quantity += quantity;
+        if let Some(cost) = cost {
+            self.priced_quantity += quantity;
+            self.cost += cost;
+        }
+    }
+
+    /// Removes `quantity` from the position. Returns the part backed by priced
+    /// acquisitions and its USD cost; disposals beyond what is held are not matched.
+    fn dispose(&mut self, quantity: f64) -> (f64, f64) {
+        let matched = quantity.min(self.quantity);
+        let (priced, cost) = match self.method {
+            CostBasisMethod::Fifo => self.take_lots(matched),
+            CostBasisMethod::Average => self.take_share(matched),
+        };
+        self.quantity -= matched;
+        if is_flat(self.quantity, quantity) {
+            *self = Position::new(self.method);
+        }
+        (priced, cost)
+    }
+
+    fn take_lots(&mut self, mut quantity: f64) -> (f64, f64) {
+        let (mut priced, mut cost) = (0.0, 0.0);
+        while quantity > 0.0 {
+            let Some(lot) = self.lots.front_mut() else { break };
+            let taken = lot.0.min(quantity);
+            if let Some(unit_cost) = lot.1 {
+                priced += taken;
+                cost += taken * unit_cost;
+            }
+            lot.0 -= taken;
+            quantity -= taken;
+            if lot.0 <= 0.0 {
+                self.lots.pop_front();
+            }
+        }
+        (priced, cost)
+    }
+
+    /// Average cost: every unit held carries the same share of the priced quantity and
+    /// cost, so a disposal reduces both by the fraction of the position it removes.
+    fn take_share(&mut self, quantity: f64) -> (f64, f64) {
+        if self.quantity <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let fraction = quantity / self.quantity;
+        let priced = self.priced_quantity * fraction;
+        let cost = self.cost * fraction;
+        self.priced_quantity -= priced;
+        self.cost -= cost;
+        (priced, cost)
+    }
+}
+
+fn is_flat(quantity: f64, leg_amount: f64) -> bool {
+    quantity <= leg_amount.abs() * DUST
+}
+
+/// `<qty>@<usd_cost>` for an acquisition (`<qty>@` when unpriced), `-<qty>` for a disposal.
+fn encode_event(leg: &CostBasisLeg) -> String {
+    match (leg.amount > 0.0, leg.priced) {
+        (true, true) => format!("{}@{}", leg.amount, leg.usd_value),
+        (true, false) => format!("{}@", leg.amount),
+        (false, _) => leg.amount.to_string(),
+    }
+}
+
+fn position_key(kind: &str, owner: &str, mint: &str) -> String {
+    format!("{}:{}:{}", kind, owner, mint)
+}
+
+fn event_prefix(owner: &str, mint: &str) -> String {
+    format!("event:{}:{}:", owner, mint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(ordinal: u64, amount: f64, price: f64) -> CostBasisLeg {
+        CostBasisLeg {
+            owner: "OWNER".to_string(),
+            mint: "MINT".to_string(),
+            amount,
+            usd_value: amount.abs() * price,
+            priced: true,
+            ordinal,
+            ..Default::default()
+        }
+    }
+
+    fn realized(method: &str, legs: Vec<CostBasisLeg>) -> Vec<(f64, f64)> {
+        let pnls = __impl_map_realized_pnl(
+            format!("method={}", method),
+            CostBasisLegs { legs },
+            StoreGetFloat64::new(0),
+            StoreGetString::new(0),
+        )
+        .unwrap();
+        pnls.items.iter().map(|pnl| (pnl.matched_amount, pnl.cost_basis_usd)).collect()
+    }
+
+    #[test]
+    fn closed_position_does_not_carry_into_the_next_one() {
+        let round_trips = || vec![leg(0, 10.0, 1.0), leg(1, -10.0, 2.0), leg(2, 10.0, 3.0), leg(3, -10.0, 4.0)];
+        assert_eq!(realized("average", round_trips()), vec![(10.0, 10.0), (10.0, 30.0)]);
+        assert_eq!(realized("fifo", round_trips()), vec![(10.0, 10.0), (10.0, 30.0)]);
+    }
+
+    #[test]
+    fn average_cost_shrinks_with_each_disposal() {
+        let legs = vec![leg(0, 10.0, 1.0), leg(1, 10.0, 3.0), leg(2, -5.0, 4.0), leg(3, 10.0, 5.0), leg(4, -25.0, 4.0)];
+        // 20 held at $2 average; selling 5 leaves 15 worth $30, buying 10 more at $5 makes 25 worth $80.
+        assert_eq!(realized("average", legs), vec![(5.0, 10.0), (25.0, 80.0)]);
+    }
+
+    #[test]
+    fn replays_stored_events() {
+        let mut position = Position::new(CostBasisMethod::Fifo);
+        for event in ["10@10", "-10", "4@", "6@18"] {
+            position.replay(event);
+        }
+        // The unpriced lot is consumed first and does not count as matched.
+        assert_eq!(position.dispose(8.0), (4.0, 12.0));
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::balances::NETWORK_FEE;
+use crate::cost_basis::{is_usd_stable, swap_legs_by_owner};
+use crate::pb::sf::jupiter::v1::BalanceChanges;
+use crate::token_price_store::swap_prices;
+use chrono::{DateTime, Days};
+use substreams::pb::substreams::store_delta::Operation;
+use substreams::pb::substreams::Clock;
+use substreams::store::{DeltaFloat64, Deltas, StoreAdd, StoreAddFloat64, StoreDelete, StoreNew};
+
+/// Daily rollup tables, the dimensions their row keys are made of and the running
+/// totals each row holds. Every total of a row is added to whenever the row is, so a
+/// block's deltas always carry the full row.
+pub(crate) const DAILY_TABLES: [(&str, &[&str], &[&str]); 3] = [
+    ("daily_mint_volumes", &["block_date", "mint"], &["volume", "volume_usd", "swap_legs"]),
+    ("daily_wallet_flows", &["block_date", "owner", "mint"], &["net_flow", "inflow", "outflow"]),
+    ("daily_fees", &["block_date"], &["network_fees", "fee_payments"]),
+];
+
+/// Running daily totals keyed `<table>:<column>:<dimension>[:<dimension>..]`:
+/// - `daily_mint_volumes`: absolute swap leg amounts per mint, their USD value where a
+///   stablecoin leg prices them, and the number of legs.
+/// - `daily_wallet_flows`: net, incoming and outgoing amounts per owner and mint.
+/// - `daily_fees`: network fees in SOL and the number of fee payments.
+///
+/// Totals follow whatever `map_balance_changes` params filtered. Only the current day is
+/// kept: every block deletes the previous day, whose rows `db_out` already upserted with
+/// their final totals.
+#[substreams::handlers::store]
+pub fn store_daily_totals(clock: Clock, changes: BalanceChanges, store: StoreAddFloat64) {
+    delete_previous_day(&clock, &store);
+
+    let groups = swap_legs_by_owner(&changes);
+    let usd_by_ordinal: HashMap<u64, f64> =
+        swap_prices(&groups).iter().map(|price| (price.leg.ordinal, price.usd_volume)).collect();
+
+    for leg in groups.iter().flatten() {
+        let date = &leg.change.block_date;
+        let mint = &leg.change.mint;
+        let usd = match is_usd_stable(mint) {
+            true => leg.amount.abs(),
+            false => usd_by_ordinal.get(&leg.ordinal).copied().unwrap_or(0.0),
+        };
+        store.add(leg.ordinal, daily_key("daily_mint_volumes", "volume", &[date, mint]), leg.amount.abs());
+        store.add(leg.ordinal, daily_key("daily_mint_volumes", "volume_usd", &[date, mint]), usd);
+        store.add(leg.ordinal, daily_key("daily_mint_volumes", "swap_legs", &[date, mint]), 1.0);
+    }
+
+    for (ordinal, change) in changes.params.iter().enumerate() {
+        let ordinal = ordinal as u64;
+        let amount: f64 = match change.change_amount.parse() {
+            Ok(amount) => amount,
+            Err(_) => continue,
+        };
+        let date = &change.block_date;
+
+        if change.change_type == NETWORK_FEE {
+            store.add(ordinal, daily_key("daily_fees", "network_fees", &[date]), -amount);
+            store.add(ordinal, daily_key("daily_fees", "fee_payments", &[date]), 1.0);
+        }
+
+        let dimensions = [date.as_str(), change.owner.as_str(), change.mint.as_str()];
+        store.add(ordinal, daily_key("daily_wallet_flows", "net_flow", &dimensions), amount);
+        store.add(ordinal, daily_key("daily_wallet_flows", "inflow", &dimensions), amount.max(0.0));
+        store.add(ordinal, daily_key("daily_wallet_flows", "outflow", &dimensions), (-amount).max(0.0));
+    }
+}
+
+fn delete_previous_day(clock: &Clock, store: &impl StoreDelete) {
+    let timestamp = clock.timestamp.as_ref().map(|t| t.seconds).unwrap_or(0);
+    let previous_day = match DateTime::from_timestamp(timestamp, 0).and_then(|time| time.checked_sub_days(Days::new(1))) {
+        Some(time) => time.format("%Y-%m-%d").to_string(),
+        None => return,
+    };
+    // The date is every table's first dimension.
+    for (table, _, columns) in DAILY_TABLES {
+        for column in columns {
+            store.delete_prefix(0, &daily_key(table, column, &[&previous_day]));
+        }
+    }
+}
+
+/// One rollup row as of the end of the block.
+pub(crate) struct DailyRow {
+    pub table: &'static str,
+    pub key: String,
+    pub dimensions: Vec<(&'static str, String)>,
+    pub totals: Vec<(String, f64)>,
+}
+
+/// Folds the block's store deltas into rows, keeping each total's last value. Deletions
+/// of closed days are not rows.
+pub(crate) fn daily_rows(deltas: &Deltas<DeltaFloat64>) -> Vec<DailyRow> {
+    let mut rows: Vec<DailyRow> = Vec::new();
+
+    for delta in deltas.deltas.iter().filter(|delta| delta.operation != Operation::Delete) {
+        let mut parts = delta.key.splitn(3, ':');
+        let (table, column, key) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(table), Some(column), Some(key)) => (table, column, key),
+            _ => continue,
+        };
+        let (table, dimension_names) = match DAILY_TABLES.iter().find(|(name, _, _)| *name == table) {
+            Some((name, dimensions, _)) => (*name, *dimensions),
+            None => continue,
+        };
+
+        let row = match rows.iter().position(|row| row.table == table && row.key == key) {
+            Some(position) => &mut rows[position],
+            None => {
+                let dimensions = dimension_names.iter().copied().zip(key.split(':').map(str::to_string)).collect();
+                rows.push(DailyRow { table, key: key.to_string(), dimensions, totals: Vec::new() });
+                rows.last_mut().expect("row was just pushed")
+            }
+        };
+        match row.totals.iter_mut().find(|(name, _)| name == column) {
+            Some((_, value)) => *value = delta.new_value,
+            None => row.totals.push((column.to_string(), delta.new_value)),
+        }
+    }
+
+    rows
+}
+
+fn daily_key(table: &str, column: &str, dimensions: &[&str]) -> String {
+    format!("{}:{}:{}", table, column, dimensions.join(":"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(key: &str, new_value: f64) -> DeltaFloat64 {
+        DeltaFloat64 { operation: Operation::Update, ordinal: 0, key: key.to_string(), old_value: 0.0, new_value }
+    }
+
+    #[test]
+    fn folds_deltas_into_rows_with_last_values() {
+        let deltas = Deltas {
+            deltas: vec![
+                delta("daily_wallet_flows:net_flow:2024-01-01:OWNER:MINT", 1.0),
+                delta("daily_wallet_flows:inflow:2024-01-01:OWNER:MINT", 1.0),
+                delta("daily_fees:network_fees:2024-01-01", 0.5),
+                delta("daily_wallet_flows:net_flow:2024-01-01:OWNER:MINT", 3.0),
+            ],
+        };
+
+        let rows = daily_rows(&deltas);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].table, "daily_wallet_flows");
+        assert_eq!(rows[0].key, "2024-01-01:OWNER:MINT");
+        assert_eq!(
+            rows[0].dimensions,
+            vec![
+                ("block_date", "2024-01-01".to_string()),
+                ("owner", "OWNER".to_string()),
+                ("mint", "MINT".to_string())
+            ]
+        );
+        assert_eq!(rows[0].totals, vec![("net_flow".to_string(), 3.0), ("inflow".to_string(), 1.0)]);
+        assert_eq!(rows[1].dimensions, vec![("block_date", "2024-01-01".to_string())]);
+    }
+
+    #[test]
+    fn deleted_days_are_not_rows() {
+        let deltas = Deltas {
+            deltas: vec![
+                DeltaFloat64 { operation: Operation::Delete, ..delta("daily_fees:network_fees:2024-01-01", 0.0) },
+                delta("daily_fees:network_fees:2024-01-02", 0.5),
+            ],
+        };
+
+        let rows = daily_rows(&deltas);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, "2024-01-02");
+    }
+}
@@ -0,0 +1,446 @@
+use crate::daily_totals::daily_rows;
+use crate::pb::sf::jupiter::v1::{
+    BalanceChange, BalanceChanges, OrderEvent, OrderEvents, PriceUpdates, SwapHops, Trades, TransactionSummaries,
+};
+use crate::sink_tables::SinkTables;
+use substreams::store::{DeltaFloat64, Deltas};
+use substreams_database_change::pb::database::DatabaseChanges;
+use substreams_database_change::tables::Tables;
+
+/// Writes every decoded entity to its own table. Params select the tables, e.g.
+/// `tables=swaps,swap_routes` or `exclude_tables=candles`; empty params write them all.
+#[substreams::handlers::map]
+pub fn db_out(
+    params: String,
+    changes: BalanceChanges,
+    transactions: TransactionSummaries,
+    trades: Trades,
+    routes: SwapHops,
+    orders: OrderEvents,
+    prices: PriceUpdates,
+    daily: Deltas<DeltaFloat64>,
+) -> Result<DatabaseChanges, substreams::errors::Error> {
+    let selected = SinkTables::parse(&params)?;
+    let mut tables = Tables::new();
+
+    for change in changes.params.into_iter().filter(|_| selected.includes("wallet_balance_changes")) {
+        // Tables and columns are declared in schema.sql and schema.clickhouse.sql; keys that are
+        // not a column land in row_key.
+        let key = balance_change_key(&change);
+
+        tables
+            .create_row("wallet_balance_changes", key)
+            .set("block_date", change.block_date)
+            .set("block_time", change.block_time)
+            .set("block_slot", change.block_slot)
+            .set("tx_id", change.tx_id)
+            .set("owner", change.owner)
+            .set("mint", change.mint)
+            .set("change_amount", change.change_amount)
+            .set("new_balance", change.new_balance)
+            .set("raw_new_balance", change.raw_new_balance)
+            .set("decimals", change.decimals)
+            .set("change_type", change.change_type)
+            .set("tx_change_type", change.tx_change_type)
+            .set("labels", change.labels.join(","))
+            .set("invoker_label", change.invoker_label)
+            .set("cpi_path", change.cpi_path)
+            .set("category", change.category)
+            .set("network_fee", change.network_fee.to_string())
+            .set("tx_index", change.tx_index)
+            .set("instruction_index", change.instruction_index)
+            .set("inner_instruction_index", change.inner_instruction_index)
+            .set("stack_height", change.stack_height)
+            .set("id", change.id)
+            .set("token_account", change.token_account)
+            .set("account_index", change.account_index)
+            .set("native", change.native);
+    }
+
+    for transaction in transactions.transactions.into_iter().filter(|_| selected.includes("transactions")) {
+        // One row per signature; balance and swap rows join on tx_id
+        tables
+            .create_row("transactions", transaction.tx_id.clone())
+            .set("block_date", transaction.block_date)
+            .set("block_time", transaction.block_time)
+            .set("block_slot", transaction.block_slot)
+            .set("tx_id", transaction.tx_id)
+            .set("fee_payer", transaction.fee_payer)
+            .set("signers", transaction.signers.join(","))
+            .set("success", transaction.success)
+            .set("fee", transaction.fee.to_string())
+            .set("compute_units_consumed", transaction.compute_units_consumed)
+            .set("programs", transaction.programs.join(","))
+            .set("jupiter_version", transaction.jupiter_version)
+            .set("change_type", transaction.change_type)
+            .set("labels", transaction.labels.join(","))
+            .set("invoker_label", transaction.invoker_label)
+            .set("tx_index", transaction.tx_index)
+            .set("id", transaction.id);
+    }
+
+    for trade in trades.trades.into_iter().filter(|_| selected.includes("swaps")) {
+        // One trade per signer and transaction
+        tables
+            .create_row("swaps", format!("{}:{}", trade.tx_id, trade.trader))
+            .set("block_date", trade.block_date)
+            .set("block_time", trade.block_time)
+            .set("block_slot", trade.block_slot)
+            .set("tx_id", trade.tx_id)
+            .set("trader", trade.trader)
+            .set("change_type", trade.change_type)
+            .set("sold_mint", trade.sold_mint)
+            .set("sold_amount", trade.sold_amount.to_string())
+            .set("bought_mint", trade.bought_mint)
+            .set("bought_amount", trade.bought_amount.to_string())
+            .set("effective_price", trade.effective_price.to_string())
+            .set("network_fee", trade.network_fee.to_string())
+            .set("rent_adjustment", trade.rent_adjustment.to_string())
+            .set("tx_index", trade.tx_index)
+            .set("instruction_index", trade.instruction_index)
+            .set("inner_instruction_index", trade.inner_instruction_index)
+            .set("stack_height", trade.stack_height)
+            .set("id", trade.id);
+    }
+
+    for hop in routes.hops.into_iter().filter(|_| selected.includes("swap_routes")) {
+        tables
+            .create_row("swap_routes", hop.id.clone())
+            .set("block_date", hop.block_date)
+            .set("block_time", hop.block_time)
+            .set("block_slot", hop.block_slot)
+            .set("tx_id", hop.tx_id)
+            .set("trader", hop.trader)
+            .set("route_id", hop.route_id)
+            .set("hop_index", hop.hop_index)
+            .set("program_id", hop.program_id)
+            .set("dex", hop.dex)
+            .set("input_mint", hop.input_mint)
+            .set("input_amount", hop.input_amount)
+            .set("output_mint", hop.output_mint)
+            .set("output_amount", hop.output_amount)
+            .set("tx_index", hop.tx_index)
+            .set("instruction_index", hop.instruction_index)
+            .set("inner_instruction_index", hop.inner_instruction_index)
+            .set("stack_height", hop.stack_height)
+            .set("id", hop.id);
+    }
+
+    for event in orders.events {
+        let table = match event.program.as_str() {
+            "LIMIT_ORDER" => "limit_orders",
+            _ => "dca_events",
+        };
+        if selected.includes(table) {
+            order_event_row(&mut tables, table, event);
+        }
+    }
+
+    for price in prices.prices.into_iter().filter(|_| selected.includes("token_prices")) {
+        tables
+            .create_row("token_prices", format!("{}:{}", price.mint, price.block_slot))
+            .set("block_date", price.block_date)
+            .set("block_time", price.block_time)
+            .set("block_slot", price.block_slot)
+            .set("mint", price.mint)
+            .set("price_usd", price.price_usd.to_string());
+    }
+
+    for candle in prices.candles.into_iter().filter(|_| selected.includes("candles")) {
+        // Each block that trades the mint rewrites the whole candle
+        tables
+            .upsert_row("candles", format!("{}:{}:{}", candle.mint, candle.interval, candle.start_time))
+            .set("mint", candle.mint)
+            .set("interval", candle.interval)
+            .set("start_time", candle.start_time)
+            .set("open", candle.open.to_string())
+            .set("high", candle.high.to_string())
+            .set("low", candle.low.to_string())
+            .set("close", candle.close.to_string())
+            .set("volume_usd", candle.volume_usd.to_string())
+            .set("block_slot", candle.block_slot);
+    }
+
+    for daily_row in daily_rows(&daily).into_iter().filter(|row| selected.includes(row.table)) {
+        // Totals come from store_daily_totals, so each upsert carries the running value
+        let row = tables.upsert_row(daily_row.table, daily_row.key);
+        for (column, value) in daily_row.dimensions {
+            row.set(column, value);
+        }
+        for (column, total) in daily_row.totals {
+            row.set(&column, total.to_string());
+        }
+    }
+
+    Ok(tables.to_database_changes())
+}
+
+/// Limit order and DCA events share one layout; each program gets its own table.
+fn order_event_row(tables: &mut Tables, table: &str, event: OrderEvent) {
+    tables
+        .create_row(table, event.id.clone())
+        .set("block_date", event.block_date)
+        .set("block_time", event.block_time)
+        .set("block_slot", event.block_slot)
+        .set("tx_id", event.tx_id)
+        .set("program_id", event.program_id)
+        .set("event", event.event)
+        .set("owner", event.user)
+        .set("order_account", event.order_account)
+        .set("status", event.status)
+        .set("accounts", event.accounts.join(","))
+        .set("in_amount", event.in_amount)
+        .set("out_amount", event.out_amount)
+        .set("in_amount_per_cycle", event.in_amount_per_cycle)
+        .set("cycle_frequency", event.cycle_frequency)
+        .set("tx_index", event.tx_index)
+        .set("instruction_index", event.instruction_index)
+        .set("inner_instruction_index", event.inner_instruction_index)
+        .set("stack_height", event.stack_height)
+        .set("id", event.id);
+}
+
+/// Primary key of a `wallet_balance_changes` row. An account's lamports and token balance
+/// each get at most one row per instruction, inner instruction and change type, so two token
+/// accounts of the same owner and mint, or SOL and wSOL rows sharing the wSOL mint, never
+/// share a key.
+pub(crate) fn balance_change_key(change: &BalanceChange) -> String {
+    format!(
+        "{}:{}:{}:{}:{}:{}:{}:{}",
+        change.tx_id,
+        change.account_index,
+        change.token_account,
+        change.mint,
+        if change.native { "native" } else { "token" },
+        change.instruction_index,
+        change.inner_instruction_index,
+        change.change_type
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    use crate::{balances, daily_totals, sink_tables};
+    use crate::constants::WRAPPED_SOL_MINT;
+    use crate::pb::sf::jupiter::v1::{Candle, SwapHop, TokenUsdPrice, Trade, TransactionSummary};
+    use substreams::pb::substreams::store_delta::Operation;
+    use substreams::store::{StoreGet, StoreGetString};
+    use substreams_solana::base58;
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, ConfirmedTransaction, Message, MessageHeader, TokenBalance, Transaction, TransactionStatusMeta,
+        UiTokenAmount, UnixTimestamp,
+    };
+
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const POSTGRES_SCHEMA: &str = include_str!("../schema.sql");
+    const CLICKHOUSE_SCHEMA: &str = include_str!("../schema.clickhouse.sql");
+    // Written with upserts, which the SQL sink applies on Postgres only.
+    const UPSERT_TABLES: [&str; 4] = ["candles", "daily_mint_volumes", "daily_wallet_flows", "daily_fees"];
+
+    fn token_balance(account_index: u32, mint: &str, owner: &[u8], amount: u64) -> TokenBalance {
+        TokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            owner: base58::encode(owner),
+            ui_token_amount: Some(UiTokenAmount {
+                ui_amount: amount as f64 / 1e6,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: (amount as f64 / 1e6).to_string(),
+            }),
+            program_id: String::new(),
+        }
+    }
+
+    /// One transaction where the signer's SOL, wSOL and two token accounts of the same
+    /// mint all change.
+    fn block_with_duplicate_owner_mint() -> Block {
+        let owner = vec![1u8; 32];
+        let account_keys = vec![owner.clone(), vec![2u8; 32], vec![3u8; 32], vec![4u8; 32]];
+
+        Block {
+            slot: 1,
+            block_time: Some(UnixTimestamp { timestamp: 1_700_000_000 }),
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys,
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    fee: 5_000,
+                    pre_balances: vec![1_000_000_000, 2_039_280, 2_039_280, 502_039_280],
+                    post_balances: vec![900_000_000, 2_039_280, 2_039_280, 602_039_280],
+                    pre_token_balances: vec![
+                        token_balance(1, MINT, &owner, 1_000_000),
+                        token_balance(2, MINT, &owner, 5_000_000),
+                        token_balance(3, WRAPPED_SOL_MINT, &owner, 500_000_000),
+                    ],
+                    post_token_balances: vec![
+                        token_balance(1, MINT, &owner, 3_000_000),
+                        token_balance(2, MINT, &owner, 4_000_000),
+                        token_balance(3, WRAPPED_SOL_MINT, &owner, 600_000_000),
+                    ],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn db_out_keeps_every_change_of_one_owner_and_mint() {
+        let changes = balances::__impl_map_balance_changes("sol=raw".to_string(), block_with_duplicate_owner_mint().into(), StoreGetString::new(0))
+            .unwrap();
+
+        // Fee and native residual on the wallet, lamports and wrapped balance of the wSOL
+        // account, and both token accounts of MINT.
+        assert_eq!(changes.params.len(), 6);
+        assert_eq!(changes.params.iter().filter(|c| c.mint == MINT).count(), 2);
+        assert_eq!(changes.params.iter().filter(|c| c.mint == WRAPPED_SOL_MINT).count(), 4);
+
+        let expected = changes.params.len();
+        let database_changes = __impl_db_out(
+            "tables=wallet_balance_changes".to_string(),
+            changes,
+            TransactionSummaries::default(),
+            Trades::default(),
+            SwapHops::default(),
+            OrderEvents::default(),
+            PriceUpdates::default(),
+            Deltas { deltas: Vec::new() },
+        )
+        .unwrap();
+        let rows = database_changes
+            .table_changes
+            .iter()
+            .filter(|row| row.table == "wallet_balance_changes")
+            .count();
+        assert_eq!(rows, expected);
+    }
+
+    /// Column types by table, read from the `CREATE TABLE` statements of a schema file.
+    fn schema_columns(schema: &str) -> HashMap<String, HashMap<String, String>> {
+        let mut tables = HashMap::new();
+        let mut current: Option<(String, HashMap<String, String>)> = None;
+
+        for line in schema.lines().map(str::trim) {
+            if let Some(rest) = line.strip_prefix("CREATE TABLE IF NOT EXISTS ") {
+                current = Some((rest.trim_end_matches(" (").to_string(), HashMap::new()));
+            } else if line.starts_with(')') {
+                if let Some((table, columns)) = current.take() {
+                    tables.insert(table, columns);
+                }
+            } else if let Some((_, columns)) = current.as_mut() {
+                if let Some((column, column_type)) = line.trim_end_matches(',').split_once(char::is_whitespace) {
+                    let end = [" PRIMARY KEY", " NOT NULL", " DEFAULT"]
+                        .iter()
+                        .filter_map(|marker| column_type.find(marker))
+                        .min()
+                        .unwrap_or(column_type.len());
+                    let column_type = column_type[..end].trim();
+                    columns.insert(column.to_string(), column_type.to_uppercase());
+                }
+            }
+        }
+
+        tables
+    }
+
+    /// Whether the sink can load `value` into a column of `column_type`.
+    fn fits(column_type: &str, value: &str) -> bool {
+        match column_type {
+            "TEXT" | "STRING" => true,
+            "DATE" => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            "BOOLEAN" | "BOOL" => value == "true" || value == "false",
+            "INTEGER" | "INT32" => value.parse::<i32>().is_ok(),
+            "BIGINT" | "INT64" => value.parse::<i64>().is_ok(),
+            "UINT32" => value.parse::<u32>().is_ok(),
+            "UINT64" | "NUMERIC(20, 0)" => value.parse::<u64>().is_ok(),
+            "NUMERIC" | "DECIMAL(38, 18)" | "DOUBLE PRECISION" | "FLOAT64" => value.parse::<f64>().is_ok(),
+            _ => false,
+        }
+    }
+
+    fn every_table_changes() -> DatabaseChanges {
+        let block = block_with_duplicate_owner_mint();
+        let changes =
+            balances::__impl_map_balance_changes("sol=raw".to_string(), block.into(), StoreGetString::new(0)).unwrap();
+        let date = "2023-11-14".to_string();
+        let order = |program: &str| OrderEvent {
+            block_date: date.clone(),
+            program: program.to_string(),
+            id: format!("1:0:0:{}", program.len()),
+            instruction_index: -1,
+            ..Default::default()
+        };
+
+        __impl_db_out(
+            String::new(),
+            changes,
+            TransactionSummaries {
+                transactions: vec![TransactionSummary { block_date: date.clone(), ..Default::default() }],
+            },
+            Trades { trades: vec![Trade { block_date: date.clone(), instruction_index: -1, ..Default::default() }] },
+            SwapHops { hops: vec![SwapHop { block_date: date.clone(), id: "1:0:0:0".to_string(), ..Default::default() }] },
+            OrderEvents { events: vec![order("LIMIT_ORDER"), order("DCA")] },
+            PriceUpdates {
+                prices: vec![TokenUsdPrice { block_date: date.clone(), ..Default::default() }],
+                candles: vec![Candle::default()],
+            },
+            Deltas {
+                deltas: daily_totals::DAILY_TABLES
+                    .iter()
+                    .map(|(table, dimensions, totals)| DeltaFloat64 {
+                        operation: Operation::Create,
+                        ordinal: 0,
+                        key: format!("{}:{}:{}", table, totals[0], vec![date.as_str(); dimensions.len()].join(":")),
+                        old_value: 0.0,
+                        new_value: 1.5,
+                    })
+                    .collect(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn db_out_fields_exist_in_shipped_schemas() {
+        let database_changes = every_table_changes();
+        let written: HashSet<&str> = database_changes.table_changes.iter().map(|row| row.table.as_str()).collect();
+        assert_eq!(written.len(), sink_tables::SINK_TABLES.len());
+
+        for (name, schema) in [("postgres", POSTGRES_SCHEMA), ("clickhouse", CLICKHOUSE_SCHEMA)] {
+            let tables = schema_columns(schema);
+            for row in &database_changes.table_changes {
+                // ClickHouse deployments exclude the tables that need upserts.
+                if name == "clickhouse" && UPSERT_TABLES.contains(&row.table.as_str()) {
+                    continue;
+                }
+                let columns = tables
+                    .get(&row.table)
+                    .unwrap_or_else(|| panic!("{} schema has no table {}", name, row.table));
+                for field in &row.fields {
+                    let column_type = columns
+                        .get(&field.name)
+                        .unwrap_or_else(|| panic!("{} schema has no column {}.{}", name, row.table, field.name));
+                    assert!(
+                        fits(column_type, &field.new_value),
+                        "{} column {}.{} of type {} cannot hold '{}'",
+                        name,
+                        row.table,
+                        field.name,
+                        column_type,
+                        field.new_value
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::pb::sf::jupiter::v1::{BalanceChange, BalanceChanges, Trade, Trades};
+use crate::pb::sf::substreams::sink::files::v1::Lines;
+use substreams::errors::Error;
+
+/// Column order of swap lines; CSV files have no header, so this order is the contract.
+pub(crate) const SWAP_COLUMNS: [&str; 18] = [
+    "block_date",
+    "block_time",
+    "block_slot",
+    "tx_id",
+    "tx_index",
+    "instruction_index",
+    "inner_instruction_index",
+    "stack_height",
+    "id",
+    "trader",
+    "change_type",
+    "sold_mint",
+    "sold_amount",
+    "bought_mint",
+    "bought_amount",
+    "effective_price",
+    "network_fee",
+    "rent_adjustment",
+];
+
+/// Column order of balance change lines.
+pub(crate) const BALANCE_CHANGE_COLUMNS: [&str; 20] = [
+    "block_date",
+    "block_time",
+    "block_slot",
+    "tx_id",
+    "tx_index",
+    "instruction_index",
+    "inner_instruction_index",
+    "stack_height",
+    "id",
+    "owner",
+    "mint",
+    "token_account",
+    "account_index",
+    "native",
+    "change_type",
+    "tx_change_type",
+    "change_amount",
+    "new_balance",
+    "decimals",
+    "network_fee",
+];
+
+/// Lines for the files sink. Params:
+/// - `format=jsonl|csv`: JSON objects (default) or RFC 4180 rows in the column order of
+///   `SWAP_COLUMNS` / `BALANCE_CHANGE_COLUMNS`.
+/// - `records=swaps,balance_changes`: what to serialize, both by default. JSON lines carry
+///   a leading `record` key; CSV needs exactly one record type so every row has the same
+///   columns.
+#[substreams::handlers::map]
+pub fn files_out(params: String, changes: BalanceChanges, trades: Trades) -> Result<Lines, Error> {
+    let params = FileParams::parse(&params)?;
+    let mut lines = Vec::new();
+
+    if params.swaps {
+        for trade in &trades.trades {
+            lines.push(params.format.line("swap", &SWAP_COLUMNS, &swap_cells(trade)));
+        }
+    }
+    if params.balance_changes {
+        for change in &changes.params {
+            lines.push(params.format.line("balance_change", &BALANCE_CHANGE_COLUMNS, &balance_change_cells(change)));
+        }
+    }
+
+    Ok(Lines { lines })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Jsonl,
+    Csv,
+}
+
+impl Format {
+    fn line(self, record: &str, columns: &[&str], cells: &[Cell]) -> String {
+        match self {
+            Format::Jsonl => {
+                let mut line = format!("{{\"record\":{}", json_string(record));
+                for (column, cell) in columns.iter().zip(cells) {
+                    line.push_str(&format!(",{}:{}", json_string(column), cell.json()));
+                }
+                line.push('}');
+                line
+            }
+            Format::Csv => cells.iter().map(Cell::csv).collect::<Vec<_>>().join(","),
+        }
+    }
+}
+
+struct FileParams {
+    format: Format,
+    swaps: bool,
+    balance_changes: bool,
+}
+
+impl FileParams {
+    fn parse(params: &str) -> Result<Self, Error> {
+        let mut format = Format::Jsonl;
+        let mut records: Option<(bool, bool)> = None;
+
+        for segment in params.split('&').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = match segment.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(anyhow::anyhow!("files_out param '{}' is not key=value", segment)),
+            };
+            match key {
+                "format" => {
+                    format = match value {
+                        "jsonl" => Format::Jsonl,
+                        "csv" => Format::Csv,
+                        _ => return Err(anyhow::anyhow!("invalid files_out format '{}', expected 'jsonl' or 'csv'", value)),
+                    }
+                }
+                "records" => {
+                    let (swaps, balance_changes) = records.get_or_insert((false, false));
+                    for record in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                        match record {
+                            "swaps" => *swaps = true,
+                            "balance_changes" => *balance_changes = true,
+                            _ => {
+                                return Err(anyhow::anyhow!(
+                                    "unknown files_out record '{}', expected 'swaps' or 'balance_changes'",
+                                    record
+                                ))
+                            }
+                        }
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("unknown files_out param '{}'", key)),
+            }
+        }
+
+        let (swaps, balance_changes) = records.unwrap_or((true, true));
+        if format == Format::Csv && swaps == balance_changes {
+            return Err(anyhow::anyhow!("format=csv needs exactly one of records=swaps or records=balance_changes"));
+        }
+        Ok(FileParams { format, swaps, balance_changes })
+    }
+}
+
+/// A typed value, so JSON keeps numbers and booleans unquoted.
+enum Cell {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Cell {
+    fn json(&self) -> String {
+        match self {
+            Cell::Text(value) => json_string(value),
+            Cell::Int(value) => value.to_string(),
+            Cell::Float(value) if value.is_finite() => value.to_string(),
+            Cell::Float(_) => "null".to_string(),
+            Cell::Bool(value) => value.to_string(),
+        }
+    }
+
+    fn csv(&self) -> String {
+        match self {
+            Cell::Text(value) => csv_field(value),
+            Cell::Int(value) => value.to_string(),
+            Cell::Float(value) if value.is_finite() => value.to_string(),
+            Cell::Float(_) => String::new(),
+            Cell::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+fn swap_cells(trade: &Trade) -> Vec<Cell> {
+    vec![
+        Cell::Text(trade.block_date.clone()),
+        Cell::Int(trade.block_time as i64),
+        Cell::Int(trade.block_slot as i64),
+        Cell::Text(trade.tx_id.clone()),
+        Cell::Int(trade.tx_index as i64),
+        Cell::Int(trade.instruction_index as i64),
+        Cell::Int(trade.inner_instruction_index as i64),
+        Cell::Int(trade.stack_height as i64),
+        Cell::Text(trade.id.clone()),
+        Cell::Text(trade.trader.clone()),
+        Cell::Text(trade.change_type.clone()),
+        Cell::Text(trade.sold_mint.clone()),
+        Cell::Float(trade.sold_amount),
+        Cell::Text(trade.bought_mint.clone()),
+        Cell::Float(trade.bought_amount),
+        Cell::Float(trade.effective_price),
+        Cell::Float(trade.network_fee),
+        Cell::Float(trade.rent_adjustment),
+    ]
+}
+
+/// Amounts stay text: `map_balance_changes` already renders them as decimals.
+fn balance_change_cells(change: &BalanceChange) -> Vec<Cell> {
+    vec![
+        Cell::Text(change.block_date.clone()),
+        Cell::Int(change.block_time as i64),
+        Cell::Int(change.block_slot as i64),
+        Cell::Text(change.tx_id.clone()),
+        Cell::Int(change.tx_index as i64),
+        Cell::Int(change.instruction_index as i64),
+        Cell::Int(change.inner_instruction_index as i64),
+        Cell::Int(change.stack_height as i64),
+        Cell::Text(change.id.clone()),
+        Cell::Text(change.owner.clone()),
+        Cell::Text(change.mint.clone()),
+        Cell::Text(change.token_account.clone()),
+        Cell::Int(change.account_index as i64),
+        Cell::Bool(change.native),
+        Cell::Text(change.change_type.clone()),
+        Cell::Text(change.tx_change_type.clone()),
+        Cell::Text(change.change_amount.clone()),
+        Cell::Text(change.new_balance.clone()),
+        Cell::Int(change.decimals as i64),
+        Cell::Float(change.network_fee),
+    ]
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Quotes fields holding a separator, quote or line break, doubling inner quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_json_and_csv() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn keeps_column_order_per_format() {
+        let trade = Trade {
+            tx_id: "sig".to_string(),
+            sold_amount: 1.5,
+            change_type: "SWAP,X".to_string(),
+            ..Default::default()
+        };
+        let cells = swap_cells(&trade);
+        assert_eq!(cells.len(), SWAP_COLUMNS.len());
+
+        let json = Format::Jsonl.line("swap", &SWAP_COLUMNS, &cells);
+        assert!(json.starts_with("{\"record\":\"swap\",\"block_date\":\"\",\"block_time\":0,"));
+        assert!(json.contains("\"change_type\":\"SWAP,X\",\"sold_mint\":\"\",\"sold_amount\":1.5,"));
+
+        let csv = Format::Csv.line("swap", &SWAP_COLUMNS, &cells);
+        assert_eq!(csv, ",0,0,sig,0,0,0,0,,,\"SWAP,X\",,1.5,,0,0,0,0");
+        assert_eq!(balance_change_cells(&BalanceChange::default()).len(), BALANCE_CHANGE_COLUMNS.len());
+    }
+
+    #[test]
+    fn csv_needs_one_record_type() {
+        assert!(FileParams::parse("format=csv").is_err());
+        assert!(FileParams::parse("format=csv&records=swaps").is_ok());
+        assert!(FileParams::parse("records=trades").is_err());
+        let params = FileParams::parse("").unwrap();
+        assert_eq!((params.format, params.swaps, params.balance_changes), (Format::Jsonl, true, true));
+    }
+}
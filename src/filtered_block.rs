@@ -0,0 +1,141 @@
+use crate::classification::Classifier;
+use crate::instructions::{account_keys, is_vote};
+use crate::pb::sf::jupiter::block::v1::{FilteredBlock, FilteredTransaction};
+use crate::program_keys::{DCA_PROGRAM_KEY, JUPITER_PROGRAM_KEYS, LIMIT_ORDER_PROGRAM_KEY};
+use substreams::errors::Error;
+use substreams_solana::pb::sf::solana::r#type::v1::{Block, ConfirmedTransaction};
+
+/// The transactions every other module reads: non-vote transactions whose account keys,
+/// static or loaded, include a registry program. The registry is the Jupiter, limit order
+/// and DCA programs plus every labelled program of the classifier, so
+/// `label.<program_id>=<LABEL>` params register more programs.
+///
+/// Each kept transaction carries its position in the block, so `tx_index` and record ids
+/// do not depend on what was dropped.
+#[substreams::handlers::map]
+pub fn map_filtered_block(params: String, block: Block) -> Result<FilteredBlock, Error> {
+    let registry = registry_keys(&Classifier::from_params(&params)?);
+    let mut filtered = FilteredBlock::from(block);
+    filtered.transactions.retain(|trx| trx.transaction.as_ref().is_some_and(|trx| is_kept(trx, &registry)));
+
+    Ok(filtered)
+}
+
+/// Every transaction of the block, e.g. to run the downstream handlers on a whole block.
+impl From<Block> for FilteredBlock {
+    fn from(block: Block) -> Self {
+        FilteredBlock {
+            slot: block.slot,
+            blockhash: block.blockhash,
+            block_time: block.block_time,
+            transactions: block
+                .transactions
+                .into_iter()
+                .enumerate()
+                .map(|(tx_index, trx)| FilteredTransaction { tx_index: tx_index as u32, transaction: Some(trx) })
+                .collect(),
+        }
+    }
+}
+
+impl FilteredBlock {
+    /// Kept transactions with their position in the block.
+    pub fn indexed_transactions(&self) -> impl Iterator<Item = (u32, &ConfirmedTransaction)> {
+        self.transactions.iter().filter_map(|trx| Some((trx.tx_index, trx.transaction.as_ref()?)))
+    }
+}
+
+/// Raw keys of the registry programs, sorted for binary search.
+fn registry_keys(classifier: &Classifier) -> Vec<[u8; 32]> {
+    let mut keys: Vec<[u8; 32]> = JUPITER_PROGRAM_KEYS
+        .into_iter()
+        .chain([LIMIT_ORDER_PROGRAM_KEY, DCA_PROGRAM_KEY])
+        .chain(classifier.programs().copied())
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+fn is_kept(trx: &ConfirmedTransaction, registry: &[[u8; 32]]) -> bool {
+    let (meta, message) = match (&trx.meta, trx.transaction.as_ref().and_then(|t| t.message.as_ref())) {
+        (Some(meta), Some(message)) => (meta, message),
+        _ => return false,
+    };
+    !is_vote(message, meta)
+        && account_keys(message, meta).into_iter().any(|key| {
+            <&[u8; 32]>::try_from(key.as_slice()).is_ok_and(|key| registry.binary_search(key).is_ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    use crate::constants::{DCA_PROGRAM_ID, JUPITER_PROGRAM_IDS, TOKEN_PROGRAM_ID, VOTE_PROGRAM_ID};
+    use substreams_solana::base58;
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        CompiledInstruction, Message, Transaction, TransactionStatusMeta,
+    };
+
+    fn transaction(program: &str, loaded: Option<&str>) -> ConfirmedTransaction {
+        ConfirmedTransaction {
+            transaction: Some(Transaction {
+                signatures: vec![vec![9u8; 64]],
+                message: Some(Message {
+                    account_keys: vec![vec![1u8; 32], base58::decode(program).unwrap()],
+                    instructions: vec![CompiledInstruction { program_id_index: 1, ..Default::default() }],
+                    ..Default::default()
+                }),
+            }),
+            meta: Some(TransactionStatusMeta {
+                loaded_readonly_addresses: loaded.into_iter().map(|key| base58::decode(key).unwrap()).collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Programs of the `blockFilter` query a module declares in substreams.yaml.
+    fn block_filter_programs(module: &str) -> BTreeSet<String> {
+        let manifest = include_str!("../substreams.yaml");
+        let start = manifest.find(&format!("- name: {}\n", module)).expect("module is declared");
+        let query = manifest[start..]
+            .lines()
+            .take_while(|line| !line.trim_start().starts_with("inputs:"))
+            .find_map(|line| line.trim().strip_prefix("string: "))
+            .expect("module declares a blockFilter query");
+        query.trim_matches('"').split(" || ").map(|key| key.trim_start_matches("program:").to_string()).collect()
+    }
+
+    #[test]
+    fn block_filter_covers_the_default_registry() {
+        let registry: BTreeSet<String> = registry_keys(&Classifier::default()).iter().map(base58::encode).collect();
+        assert_eq!(block_filter_programs("map_filtered_block"), registry);
+    }
+
+    #[test]
+    fn keeps_registry_transactions_at_their_position() {
+        let block = Block {
+            slot: 7,
+            transactions: vec![
+                transaction(VOTE_PROGRAM_ID, None),
+                transaction(JUPITER_PROGRAM_IDS[0], None),
+                // Reaches the DCA program through a lookup table.
+                transaction(TOKEN_PROGRAM_ID, Some(DCA_PROGRAM_ID)),
+                transaction(TOKEN_PROGRAM_ID, None),
+            ],
+            ..Default::default()
+        };
+
+        let filtered = __impl_map_filtered_block(String::new(), block.clone()).unwrap();
+        assert_eq!(filtered.slot, 7);
+        let kept: Vec<(u32, &ConfirmedTransaction)> = filtered.indexed_transactions().collect();
+        assert_eq!(kept, vec![(1, &block.transactions[1]), (2, &block.transactions[2])]);
+
+        // A label rule registers its program.
+        let params = format!("label.{}=CUSTOM", TOKEN_PROGRAM_ID);
+        let filtered = __impl_map_filtered_block(params, block).unwrap();
+        assert_eq!(filtered.indexed_transactions().map(|(tx_index, _)| tx_index).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
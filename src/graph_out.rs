@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::db_out::balance_change_key;
+use crate::pb::sf::jupiter::v1::{BalanceChanges, OrderEvents, SwapHops, Trades};
+use crate::pb::sf::substreams::sink::entity::v1::entity_change::Operation;
+use crate::pb::sf::substreams::sink::entity::v1::value::Typed;
+use crate::pb::sf::substreams::sink::entity::v1::{Array, EntityChange, EntityChanges, Field, Value};
+use substreams::errors::Error;
+use substreams::pb::substreams::store_delta::Operation as DeltaOperation;
+use substreams::scalar::BigInt;
+use substreams::store::{DeltaBigInt, Deltas};
+
+/// Entity changes for the subgraph in `schema.graphql`. Every change is an upsert, so
+/// `Token` and `Wallet` are rewritten with their last slot whenever a record uses them,
+/// and relationships such as `wallet.swaps` are derived by Graph Node. `LimitOrder` and
+/// `DcaPosition` are keyed by their account: each event updates the fields it knows,
+/// with fill totals from `store_order_totals`.
+#[substreams::handlers::map]
+pub fn graph_out(
+    changes: BalanceChanges,
+    trades: Trades,
+    routes: SwapHops,
+    orders: OrderEvents,
+    order_totals: Deltas<DeltaBigInt>,
+) -> Result<EntityChanges, Error> {
+    let mut entities = Entities::default();
+
+    for trade in trades.trades {
+        entities.wallet(&trade.trader, trade.block_slot);
+        entities.token(&trade.sold_mint, trade.block_slot);
+        entities.token(&trade.bought_mint, trade.block_slot);
+        entities
+            .upsert("Swap", swap_id(&trade.tx_id, &trade.trader))
+            .string("wallet", trade.trader)
+            .strings("tokens", vec![trade.sold_mint.clone(), trade.bought_mint.clone()])
+            .string("soldToken", trade.sold_mint)
+            .bigdecimal("soldAmount", trade.sold_amount)
+            .string("boughtToken", trade.bought_mint)
+            .bigdecimal("boughtAmount", trade.bought_amount)
+            .bigdecimal("effectivePrice", trade.effective_price)
+            .bigdecimal("networkFee", trade.network_fee)
+            .bigdecimal("rentAdjustment", trade.rent_adjustment)
+            .string("changeType", trade.change_type)
+            .string("txId", trade.tx_id)
+            .bigint("blockSlot", trade.block_slot)
+            .bigint("blockTime", trade.block_time)
+            .string("recordId", trade.id);
+    }
+
+    for hop in routes.hops {
+        entities.wallet(&hop.trader, hop.block_slot);
+        let entity = entities
+            .upsert("SwapHop", hop.id.clone())
+            .string("swap", swap_id(&hop.tx_id, &hop.trader))
+            .string("wallet", hop.trader)
+            .string("routeId", hop.route_id)
+            .int("hopIndex", hop.hop_index as i32)
+            .string("programId", hop.program_id)
+            .string("dex", hop.dex)
+            .bigint("inputAmount", hop.input_amount)
+            .bigint("outputAmount", hop.output_amount)
+            .string("txId", hop.tx_id)
+            .bigint("blockSlot", hop.block_slot)
+            .bigint("blockTime", hop.block_time);
+        // Hops whose transfers carry no known mint leave the token unset.
+        if !hop.input_mint.is_empty() {
+            entity.string("inputToken", hop.input_mint.clone());
+        }
+        if !hop.output_mint.is_empty() {
+            entity.string("outputToken", hop.output_mint.clone());
+        }
+        for mint in [hop.input_mint, hop.output_mint].iter().filter(|mint| !mint.is_empty()) {
+            entities.token(mint, hop.block_slot);
+        }
+    }
+
+    // Running fill totals per order account, as of the account's last event in the block.
+    let totals: HashMap<&str, &BigInt> = order_totals
+        .deltas
+        .iter()
+        .filter(|delta| delta.operation != DeltaOperation::Delete)
+        .map(|delta| (delta.key.as_str(), &delta.new_value))
+        .collect();
+
+    for event in orders.events.into_iter().filter(|event| !event.order_account.is_empty()) {
+        if !event.user.is_empty() {
+            entities.wallet(&event.user, event.block_slot);
+        }
+        let total = |name: &str| totals.get(format!("{}:{}", event.order_account, name).as_str()).copied().cloned();
+        let (filled, fills, deposited) = (total("filled"), total("fills"), total("in"));
+        let opened = matches!(event.event.as_str(), "initialize_order" | "open_dca" | "open_dca_v2");
+
+        let entity = match event.program.as_str() {
+            "LIMIT_ORDER" => {
+                let entity = entities.upsert("LimitOrder", event.order_account.clone());
+                if opened {
+                    entity.bigint("makingAmount", event.in_amount).bigint("takingAmount", event.out_amount);
+                }
+                if let Some(filled) = filled {
+                    entity.bigint("filledMakingAmount", filled);
+                }
+                entity
+            }
+            _ => {
+                let entity = entities.upsert("DcaPosition", event.order_account.clone());
+                if opened {
+                    entity
+                        .bigint("inAmountPerCycle", event.in_amount_per_cycle)
+                        .bigint("cycleFrequency", event.cycle_frequency);
+                }
+                if let Some(deposited) = deposited {
+                    entity.bigint("inAmount", deposited);
+                }
+                if let Some(filled) = filled {
+                    entity.bigint("filledOutAmount", filled);
+                }
+                entity
+            }
+        };
+        if let Some(fills) = fills {
+            entity.bigint("fills", fills);
+        }
+        // Keeper fills do not name the owner; the one set at opening stays.
+        if !event.user.is_empty() {
+            entity.string("wallet", event.user);
+        }
+        entity
+            .string("status", event.status)
+            .string("lastEvent", event.event)
+            .string("programId", event.program_id)
+            .string("txId", event.tx_id)
+            .bigint("blockSlot", event.block_slot)
+            .bigint("blockTime", event.block_time);
+    }
+
+    for change in changes.params {
+        entities.wallet(&change.owner, change.block_slot);
+        entities.token(&change.mint, change.block_slot);
+        entities
+            .upsert("BalanceChange", balance_change_key(&change))
+            .string("wallet", change.owner)
+            .string("token", change.mint)
+            .string("tokenAccount", change.token_account)
+            .bigdecimal_str("changeAmount", change.change_amount)
+            .bigdecimal_str("newBalance", change.new_balance)
+            .int("decimals", change.decimals as i32)
+            .string("changeType", change.change_type)
+            .string("txChangeType", change.tx_change_type)
+            .bool("native", change.native)
+            .string("txId", change.tx_id)
+            .bigint("blockSlot", change.block_slot)
+            .bigint("blockTime", change.block_time)
+            .string("recordId", change.id);
+    }
+
+    Ok(entities.into_changes())
+}
+
+/// Swaps are one per signer and transaction, like the `swaps` table.
+fn swap_id(tx_id: &str, trader: &str) -> String {
+    format!("{}-{}", tx_id, trader)
+}
+
+#[derive(Default)]
+struct Entities {
+    changes: Vec<EntityChange>,
+    wallets: HashSet<String>,
+    tokens: HashSet<String>,
+}
+
+impl Entities {
+    fn upsert(&mut self, entity: &str, id: String) -> &mut EntityChange {
+        self.changes.push(EntityChange {
+            entity: entity.to_string(),
+            id,
+            operation: Operation::Update as i32,
+            ..Default::default()
+        });
+        self.changes.last_mut().expect("change was just pushed")
+    }
+
+    fn wallet(&mut self, address: &str, slot: u64) {
+        if self.wallets.insert(address.to_string()) {
+            self.upsert("Wallet", address.to_string()).bigint("lastSlot", slot);
+        }
+    }
+
+    fn token(&mut self, mint: &str, slot: u64) {
+        if self.tokens.insert(mint.to_string()) {
+            self.upsert("Token", mint.to_string()).bigint("lastSlot", slot);
+        }
+    }
+
+    fn into_changes(self) -> EntityChanges {
+        EntityChanges { entity_changes: self.changes }
+    }
+}
+
+/// Field setters, private to this module.
+impl EntityChange {
+    fn field(&mut self, name: &str, value: Typed) -> &mut Self {
+        self.fields.push(Field {
+            name: name.to_string(),
+            new_value: Some(Value { typed: Some(value) }),
+            old_value: None,
+        });
+        self
+    }
+
+    fn string(&mut self, name: &str, value: String) -> &mut Self {
+        self.field(name, Typed::String(value))
+    }
+
+    fn strings(&mut self, name: &str, values: Vec<String>) -> &mut Self {
+        let value = values.into_iter().map(|value| Value { typed: Some(Typed::String(value)) }).collect();
+        self.field(name, Typed::Array(Array { value }))
+    }
+
+    fn int(&mut self, name: &str, value: i32) -> &mut Self {
+        self.field(name, Typed::Int32(value))
+    }
+
+    fn bigint(&mut self, name: &str, value: impl ToString) -> &mut Self {
+        self.field(name, Typed::Bigint(value.to_string()))
+    }
+
+    fn bigdecimal(&mut self, name: &str, value: f64) -> &mut Self {
+        self.field(name, Typed::Bigdecimal(value.to_string()))
+    }
+
+    /// Decimal amounts `map_balance_changes` already renders as strings.
+    fn bigdecimal_str(&mut self, name: &str, value: String) -> &mut Self {
+        self.field(name, Typed::Bigdecimal(value))
+    }
+
+    fn bool(&mut self, name: &str, value: bool) -> &mut Self {
+        self.field(name, Typed::Bool(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::pb::sf::jupiter::v1::{BalanceChange, OrderEvent, SwapHop, Trade};
+
+    const SCHEMA: &str = include_str!("../schema.graphql");
+
+    fn total(key: &str, value: i64) -> DeltaBigInt {
+        DeltaBigInt {
+            operation: DeltaOperation::Update,
+            ordinal: 0,
+            key: key.to_string(),
+            old_value: BigInt::zero(),
+            new_value: BigInt::from(value),
+        }
+    }
+
+    fn field<'a>(change: &'a EntityChange, name: &str) -> Option<&'a Typed> {
+        change.fields.iter().find(|field| field.name == name)?.new_value.as_ref()?.typed.as_ref()
+    }
+
+    #[test]
+    fn fills_update_the_order_they_fill() {
+        let event = |event: &str, status: &str, user: &str| OrderEvent {
+            program: "LIMIT_ORDER".to_string(),
+            event: event.to_string(),
+            status: status.to_string(),
+            order_account: "ORDER".to_string(),
+            user: user.to_string(),
+            ..Default::default()
+        };
+        let changes = __impl_graph_out(
+            BalanceChanges::default(),
+            Trades::default(),
+            SwapHops::default(),
+            OrderEvents { events: vec![event("fill_order", "PARTIALLY_FILLED", "MAKER"), event("fill_order", "FILLED", "MAKER")] },
+            Deltas {
+                deltas: vec![
+                    total("ORDER:filled", 40),
+                    total("ORDER:fills", 1),
+                    total("ORDER:filled", 100),
+                    total("ORDER:fills", 2),
+                    DeltaBigInt { operation: DeltaOperation::Delete, ..total("ORDER:filled", 0) },
+                    DeltaBigInt { operation: DeltaOperation::Delete, ..total("ORDER:fills", 0) },
+                ],
+            },
+        )
+        .unwrap();
+
+        let orders: Vec<&EntityChange> = changes.entity_changes.iter().filter(|change| change.entity == "LimitOrder").collect();
+        assert_eq!(orders.len(), 2);
+        assert!(orders.iter().all(|order| order.id == "ORDER"));
+        // Opening amounts are left to the opening event.
+        assert!(field(orders[1], "makingAmount").is_none());
+        assert_eq!(field(orders[1], "status"), Some(&Typed::String("FILLED".to_string())));
+        assert_eq!(field(orders[1], "filledMakingAmount"), Some(&Typed::Bigint("100".to_string())));
+        assert_eq!(field(orders[1], "fills"), Some(&Typed::Bigint("2".to_string())));
+    }
+
+    /// Stored fields by entity, with their GraphQL type; derived fields are left out.
+    fn schema_fields() -> HashMap<String, HashMap<String, String>> {
+        let mut entities = HashMap::new();
+        let mut current: Option<(String, HashMap<String, String>)> = None;
+
+        for line in SCHEMA.lines().map(|line| line.split('#').next().unwrap_or("").trim()) {
+            if let Some(rest) = line.strip_prefix("type ") {
+                current = Some((rest.split_whitespace().next().unwrap_or("").to_string(), HashMap::new()));
+            } else if line == "}" {
+                if let Some((entity, fields)) = current.take() {
+                    entities.insert(entity, fields);
+                }
+            } else if let (Some((_, fields)), Some((name, field_type))) = (current.as_mut(), line.split_once(':')) {
+                if !field_type.contains("@derivedFrom") {
+                    fields.insert(name.trim().to_string(), field_type.trim().to_string());
+                }
+            }
+        }
+
+        entities
+    }
+
+    fn matches(field_type: &str, value: &Typed, entities: &HashMap<String, HashMap<String, String>>) -> bool {
+        let base = field_type.trim_end_matches('!');
+        match value {
+            Typed::Array(array) => {
+                let inner = base.trim_start_matches('[').trim_end_matches(']');
+                base.starts_with('[')
+                    && array.value.iter().all(|v| v.typed.as_ref().is_some_and(|t| matches(inner, t, entities)))
+            }
+            Typed::String(_) => base == "String" || base == "ID" || entities.contains_key(base),
+            Typed::Bigint(value) => base == "BigInt" && value.parse::<i128>().is_ok(),
+            Typed::Bigdecimal(value) => base == "BigDecimal" && value.parse::<f64>().is_ok(),
+            Typed::Int32(_) => base == "Int",
+            Typed::Bool(_) => base == "Boolean",
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn entity_changes_match_schema() {
+        let order = |program: &str, event: &str| OrderEvent {
+            program: program.to_string(),
+            event: event.to_string(),
+            order_account: program.to_string(),
+            status: "OPEN".to_string(),
+            ..Default::default()
+        };
+        let trade = Trade {
+            trader: "W".to_string(),
+            sold_mint: "A".to_string(),
+            bought_mint: "B".to_string(),
+            ..Default::default()
+        };
+        let hop = SwapHop {
+            trader: "W".to_string(),
+            input_mint: "A".to_string(),
+            output_mint: "B".to_string(),
+            ..Default::default()
+        };
+        let changes = __impl_graph_out(
+            BalanceChanges {
+                params: vec![BalanceChange {
+                    owner: "W".to_string(),
+                    mint: "M".to_string(),
+                    change_amount: "-1.5".to_string(),
+                    new_balance: "2".to_string(),
+                    ..Default::default()
+                }],
+            },
+            Trades { trades: vec![trade] },
+            SwapHops { hops: vec![hop] },
+            OrderEvents { events: vec![order("LIMIT_ORDER", "initialize_order"), order("DCA", "open_dca")] },
+            Deltas { deltas: vec![total("LIMIT_ORDER:filled", 0), total("DCA:in", 100)] },
+        )
+        .unwrap();
+
+        let entities = schema_fields();
+        let written: HashSet<&str> = changes.entity_changes.iter().map(|change| change.entity.as_str()).collect();
+        assert_eq!(written.len(), entities.len(), "every schema entity is written");
+
+        for change in &changes.entity_changes {
+            let fields = entities.get(&change.entity).unwrap_or_else(|| panic!("no entity {}", change.entity));
+            for field in &change.fields {
+                let field_type = fields
+                    .get(&field.name)
+                    .unwrap_or_else(|| panic!("no field {}.{}", change.entity, field.name));
+                let value = field.new_value.as_ref().and_then(|value| value.typed.as_ref()).unwrap();
+                assert!(
+                    matches(field_type, value, &entities),
+                    "{}.{} is not a {}",
+                    change.entity,
+                    field.name,
+                    field_type
+                );
+            }
+            // Every required field but the id must be set.
+            for (name, field_type) in fields.iter().filter(|(name, t)| t.ends_with('!') && *name != "id") {
+                assert!(
+                    change.fields.iter().any(|field| &field.name == name),
+                    "{}.{}: {} is not set",
+                    change.entity,
+                    name,
+                    field_type
+                );
+            }
+        }
+    }
+}
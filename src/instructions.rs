@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use crate::program_keys::{SYSTEM_PROGRAM_KEY, TOKEN_2022_PROGRAM_KEY, TOKEN_PROGRAM_KEY, VOTE_PROGRAM_KEY};
+use substreams_solana::pb::sf::solana::r#type::v1::{Message, TransactionStatusMeta};
+
+// System program instruction discriminators (u32, little endian).
+pub(crate) const SYSTEM_CREATE_ACCOUNT: u32 = 0;
+pub(crate) const SYSTEM_TRANSFER: u32 = 2;
+pub(crate) const SYSTEM_CREATE_ACCOUNT_WITH_SEED: u32 = 3;
+pub(crate) const SYSTEM_TRANSFER_WITH_SEED: u32 = 11;
+
+// SPL Token instruction discriminators.
+pub(crate) const TOKEN_TRANSFER: u8 = 3;
+pub(crate) const TOKEN_MINT_TO: u8 = 7;
+pub(crate) const TOKEN_BURN: u8 = 8;
+pub(crate) const TOKEN_CLOSE_ACCOUNT: u8 = 9;
+pub(crate) const TOKEN_TRANSFER_CHECKED: u8 = 12;
+pub(crate) const TOKEN_MINT_TO_CHECKED: u8 = 14;
+pub(crate) const TOKEN_BURN_CHECKED: u8 = 15;
+
+/// Position of an instruction within its transaction. `inner_index` is -1 for the
+/// top-level instruction itself; both are -1 for changes no instruction explains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct InstructionRef {
+    pub index: i32,
+    pub inner_index: i32,
+}
+
+impl InstructionRef {
+    pub const NONE: InstructionRef = InstructionRef { index: -1, inner_index: -1 };
+
+    /// Position of the next instruction when walking a transaction in execution order.
+    pub fn next(self, is_root: bool) -> Self {
+        match is_root {
+            true => InstructionRef { index: self.index + 1, inner_index: -1 },
+            false => InstructionRef { index: self.index, inner_index: self.inner_index + 1 },
+        }
+    }
+}
+
+/// Stable identifier of a record produced by an instruction; unattributed records use
+/// `InstructionRef::NONE`.
+pub(crate) fn record_id(slot: u64, tx_index: u32, at: InstructionRef) -> String {
+    format!("{}:{}:{}:{}", slot, tx_index, at.index, at.inner_index)
+}
+
+/// Stable identifier of a per-transaction record.
+pub(crate) fn tx_record_id(slot: u64, tx_index: u32) -> String {
+    format!("{}:{}", slot, tx_index)
+}
+
+/// CPI depth of the instruction at `at`, 0 when it is not part of the transaction.
+pub(crate) fn stack_height(instructions: &[TxInstruction], at: InstructionRef) -> u32 {
+    instructions.iter().find(|inst| inst.at == at).map(|inst| inst.stack_height).unwrap_or(0)
+}
+
+pub(crate) struct TxInstruction<'a> {
+    pub at: InstructionRef,
+    /// CPI depth, 1 for top-level instructions.
+    pub stack_height: u32,
+    /// Raw program key, empty when the index is out of range. Base58 encode it only for
+    /// the records that carry it.
+    pub program_id: &'a [u8],
+    pub accounts: &'a [u8],
+    pub data: &'a [u8],
+}
+
+impl TxInstruction<'_> {
+    pub fn account(&self, position: usize) -> Option<usize> {
+        self.accounts.get(position).map(|index| *index as usize)
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.program_id == SYSTEM_PROGRAM_KEY
+    }
+
+    pub fn is_token(&self) -> bool {
+        self.program_id == TOKEN_PROGRAM_KEY || self.program_id == TOKEN_2022_PROGRAM_KEY
+    }
+}
+
+/// Static keys followed by the addresses loaded from lookup tables, which is the
+/// order instruction account indexes refer to.
+pub(crate) fn account_keys<'a>(message: &'a Message, meta: &'a TransactionStatusMeta) -> Vec<&'a Vec<u8>> {
+    message
+        .account_keys
+        .iter()
+        .chain(meta.loaded_writable_addresses.iter())
+        .chain(meta.loaded_readonly_addresses.iter())
+        .collect()
+}
+
+/// Transactions whose instructions, CPIs included, all call the vote program. Runs on
+/// every transaction of the block, so it reads program ids without collecting them.
+pub(crate) fn is_vote(message: &Message, meta: &TransactionStatusMeta) -> bool {
+    let calls_vote = |program_id_index: u32| {
+        message
+            .account_keys
+            .iter()
+            .chain(meta.loaded_writable_addresses.iter())
+            .chain(meta.loaded_readonly_addresses.iter())
+            .nth(program_id_index as usize)
+            .is_some_and(|key| key.as_slice() == VOTE_PROGRAM_KEY)
+    };
+    !message.instructions.is_empty()
+        && message.instructions.iter().all(|inst| calls_vote(inst.program_id_index))
+        && meta
+            .inner_instructions
+            .iter()
+            .flat_map(|inner| &inner.instructions)
+            .all(|inst| calls_vote(inst.program_id_index))
+}
+
+/// Every instruction of a transaction in execution order: each top-level instruction
+/// followed by the CPIs it made.
+pub(crate) fn ordered_instructions<'a>(message: &'a Message, meta: &'a TransactionStatusMeta) -> Vec<TxInstruction<'a>> {
+    let keys = account_keys(message, meta);
+    let program_id = |index: u32| keys.get(index as usize).map(|key| key.as_slice()).unwrap_or_default();
+    let mut ordered = Vec::new();
+
+    for (index, inst) in message.instructions.iter().enumerate() {
+        ordered.push(TxInstruction {
+            at: InstructionRef { index: index as i32, inner_index: -1 },
+            stack_height: 1,
+            program_id: program_id(inst.program_id_index),
+            accounts: &inst.accounts,
+            data: &inst.data,
+        });
+        for inner in meta.inner_instructions.iter().filter(|inner| inner.index as usize == index) {
+            for (inner_index, inst) in inner.instructions.iter().enumerate() {
+                ordered.push(TxInstruction {
+                    at: InstructionRef { index: index as i32, inner_index: inner_index as i32 },
+                    // Older blocks do not record stack heights; treat those CPIs as direct.
+                    stack_height: inst.stack_height.unwrap_or(2),
+                    program_id: program_id(inst.program_id_index),
+                    accounts: &inst.accounts,
+                    data: &inst.data,
+                });
+            }
+        }
+    }
+
+    ordered
+}
+
+/// Signed balance movements per account index, summed per instruction (top-level or
+/// inner), as decoded from System and SPL Token instructions.
+#[derive(Default)]
+pub(crate) struct Movements {
+    groups: HashMap<usize, Vec<(InstructionRef, i128)>>,
+    last_touch: HashMap<usize, InstructionRef>,
+}
+
+impl Movements {
+    /// Token amounts moved by Transfer, MintTo and Burn (and their Checked variants).
+    pub fn token(instructions: &[TxInstruction]) -> Self {
+        let mut movements = Movements::default();
+
+        for inst in instructions.iter().filter(|inst| inst.is_token()) {
+            movements.touch(inst);
+            let amount = match read_u64(inst.data, 1) {
+                Some(amount) => amount as i128,
+                None => continue,
+            };
+            let (from, to) = match inst.data.first() {
+                Some(&TOKEN_TRANSFER) => (inst.account(0), inst.account(1)),
+                Some(&TOKEN_TRANSFER_CHECKED) => (inst.account(0), inst.account(2)),
+                Some(&TOKEN_MINT_TO) | Some(&TOKEN_MINT_TO_CHECKED) => (None, inst.account(1)),
+                Some(&TOKEN_BURN) | Some(&TOKEN_BURN_CHECKED) => (inst.account(0), None),
+                _ => continue,
+            };
+            movements.transfer(inst.at, from, to, amount);
+        }
+
+        movements
+    }
+
+    /// Lamports moved by System transfers. Account creation is left to rent tracking,
+    /// and token instructions that pay out lamports (closing a wSOL account) only count
+    /// as touching the account.
+    pub fn native<'a, 'b: 'a>(instructions: impl IntoIterator<Item = &'a TxInstruction<'b>>) -> Self {
+        let mut movements = Movements::default();
+
+        for inst in instructions.into_iter().filter(|inst| inst.is_system() || inst.is_token()) {
+            movements.touch(inst);
+            if !inst.is_system() {
+                continue;
+            }
+            let (from, to) = match read_u32(inst.data, 0) {
+                Some(SYSTEM_TRANSFER) => (inst.account(0), inst.account(1)),
+                Some(SYSTEM_TRANSFER_WITH_SEED) => (inst.account(0), inst.account(2)),
+                _ => continue,
+            };
+            if let Some(lamports) = read_u64(inst.data, 4) {
+                movements.transfer(inst.at, from, to, lamports as i128);
+            }
+        }
+
+        movements
+    }
+
+    /// Splits an account's net delta across the instructions that moved it, in
+    /// execution order. Whatever the decoded movements do not explain goes to the
+    /// last instruction that referenced the account, or is left unattributed.
+    pub fn split(&self, account: usize, delta: i128) -> Vec<(InstructionRef, i128)> {
+        let mut parts = self.groups.get(&account).cloned().unwrap_or_default();
+
+        let residual = delta - parts.iter().map(|(_, amount)| amount).sum::<i128>();
+        if residual != 0 {
+            match self.last_touch.get(&account) {
+                Some(at) => match parts.iter_mut().find(|(part, _)| part == at) {
+                    Some((_, amount)) => *amount += residual,
+                    None => parts.push((*at, residual)),
+                },
+                None => parts.push((InstructionRef::NONE, residual)),
+            }
+        }
+
+        parts.retain(|(_, amount)| *amount != 0);
+        parts.sort_by_key(|(at, _)| if at.index < 0 { (i32::MAX, 0) } else { (at.index, at.inner_index) });
+        parts
+    }
+
+    fn touch(&mut self, inst: &TxInstruction) {
+        for account in inst.accounts {
+            self.last_touch.insert(*account as usize, inst.at);
+        }
+    }
+
+    fn transfer(&mut self, at: InstructionRef, from: Option<usize>, to: Option<usize>, amount: i128) {
+        if let Some(from) = from {
+            self.record(from, at, -amount);
+        }
+        if let Some(to) = to {
+            self.record(to, at, amount);
+        }
+    }
+
+    fn record(&mut self, account: usize, at: InstructionRef, amount: i128) {
+        let parts = self.groups.entry(account).or_default();
+        match parts.iter_mut().find(|(part, _)| *part == at) {
+            Some((_, total)) => *total += amount,
+            None => parts.push((at, amount)),
+        }
+    }
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().expect("slice is 4 bytes")))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().expect("slice is 8 bytes")))
+}
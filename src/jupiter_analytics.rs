@@ -1,3 +1,4 @@
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 
 use crate::pb::sf::jupiter::v1::{JupiterAnalytics, JupiterInstructions, ProgramStat};
@@ -29,7 +30,8 @@ pub fn map_jupiter_analytics(instructions: JupiterInstructions) -> Result<Jupite
             instruction_count,
         })
         .collect::<Vec<_>>();
-    top_programs.sort_by(|a, b| b.instruction_count.cmp(&a.instruction_count));
+    // Ties break on the id; map iteration order would make the output nondeterministic.
+    top_programs.sort_by(|a, b| (Reverse(a.instruction_count), &a.program_id).cmp(&(Reverse(b.instruction_count), &b.program_id)));
     top_programs.truncate(5);
 
     Ok(JupiterAnalytics {
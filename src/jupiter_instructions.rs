@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::constants::JUPITER_PROGRAM_IDS;
+use crate::instructions::{record_id, InstructionRef};
+use crate::program_keys::is_jupiter_key;
 use crate::pb::sf::jupiter::v1::{
     AccountOwnerRecords, EnrichedAccount, JupiterInstruction, JupiterInstructions, TokenPriceList,
     TradingDataList,
@@ -26,15 +27,17 @@ pub fn map_jupiter_instructions(
         .map(|ts| ts.timestamp.max(0) as u64)
         .unwrap_or_default();
 
-    for trx in block.transactions() {
+    for (tx_index, trx) in block.transactions.iter().enumerate().filter(|(_, trx)| trx.is_successful()) {
         let tx_id = trx.id();
         let trade_data = trades_by_tx.get(&tx_id);
+        let mut at = InstructionRef::NONE;
 
         for instruction in trx.walk_instructions() {
-            let program_id = instruction.program_id().to_string();
-            if !is_jupiter_program(&program_id) {
+            at = at.next(instruction.is_root());
+            if !is_jupiter_key(instruction.program_id().as_ref()) {
                 continue;
             }
+            let program_id = instruction.program_id().to_string();
 
             let enriched_accounts = instruction
                 .accounts()
@@ -58,6 +61,11 @@ pub fn map_jupiter_instructions(
                 data,
                 slot: block.slot,
                 block_time,
+                tx_index: tx_index as u32,
+                instruction_index: at.index,
+                inner_instruction_index: at.inner_index,
+                stack_height: instruction.stack_height(),
+                id: record_id(block.slot, tx_index as u32, at),
             });
         }
     }
@@ -121,8 +129,3 @@ fn enrich_account(
         mint,
     }
 }
-
-fn is_jupiter_program(program_id: &str) -> bool {
-    JUPITER_PROGRAM_IDS.iter().any(|entry| entry == &program_id)
-}
-
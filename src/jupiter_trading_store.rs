@@ -1,4 +1,5 @@
-use crate::constants::JUPITER_PROGRAM_IDS;
+use crate::instructions::{record_id, InstructionRef};
+use crate::program_keys::is_jupiter_key;
 use crate::pb::sf::jupiter::v1::{TradingData, TradingDataList};
 use substreams::errors::Error;
 use substreams_solana::pb::sf::solana::r#type::v1::Block;
@@ -12,14 +13,16 @@ pub fn map_jupiter_trading_data(block: Block) -> Result<TradingDataList, Error>
         .map(|ts| ts.timestamp.max(0) as u64)
         .unwrap_or_default();
 
-    for trx in block.transactions() {
+    for (tx_index, trx) in block.transactions.iter().enumerate().filter(|(_, trx)| trx.is_successful()) {
         let tx_id = trx.id();
+        let mut at = InstructionRef::NONE;
 
         for instruction in trx.walk_instructions() {
-            let program_id = instruction.program_id().to_string();
-            if !is_jupiter_program(&program_id) {
+            at = at.next(instruction.is_root());
+            if !is_jupiter_key(instruction.program_id().as_ref()) {
                 continue;
             }
+            let program_id = instruction.program_id().to_string();
 
             let accounts = instruction
                 .accounts()
@@ -34,13 +37,14 @@ pub fn map_jupiter_trading_data(block: Block) -> Result<TradingDataList, Error>
                 data: instruction.data().clone(),
                 slot: block.slot,
                 block_time,
+                tx_index: tx_index as u32,
+                instruction_index: at.index,
+                inner_instruction_index: at.inner_index,
+                stack_height: instruction.stack_height(),
+                id: record_id(block.slot, tx_index as u32, at),
             });
         }
     }
 
     Ok(TradingDataList { items })
 }
-
-fn is_jupiter_program(program_id: &str) -> bool {
-    JUPITER_PROGRAM_IDS.iter().any(|id| id == &program_id)
-}
\ No newline at end of file
@@ -4,40 +4,64 @@ pub mod spl_account_store;
 pub mod jupiter_trading_store;
 pub mod token_price_store;
 pub mod jupiter_instructions;
-pub mod balances;
+pub mod balance_params;
 pub mod jupiter_analytics;
+pub mod trades;
+pub mod instructions;
+pub mod program_keys;
+pub mod rent;
+pub mod order_events;
+pub mod sink_tables;
+pub mod daily_totals;
+pub mod graph_out;
+pub mod wallet_balances;
+pub mod block_index;
+
+// The handler macro reads `params: String` through raw pointers on the wasm boundary,
+// which clippy flags on the generated export of every module declaring one.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod classification;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod balances;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod cost_basis;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod watchlist;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod transactions;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod swap_routes;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod files_out;
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub mod filtered_block;
+// db_out takes one input per source module, and the macro copies them onto an export an
+// attribute on the handler does not reach.
+#[allow(clippy::not_unsafe_ptr_arg_deref, clippy::too_many_arguments)]
+pub mod db_out;
 
-use substreams_database_change::pb::database::DatabaseChanges;
-use substreams_database_change::tables::Tables;
 pub use spl_account_store::map_spl_initialized_account;
 pub use jupiter_trading_store::map_jupiter_trading_data;
-pub use token_price_store::map_token_prices;
+pub use token_price_store::{
+    map_price_updates, map_token_prices, store_candle_highs, store_candle_lows, store_candle_opens,
+    store_candle_volumes, store_token_usd_prices,
+};
 pub use jupiter_instructions::map_jupiter_instructions;
 pub use jupiter_analytics::map_jupiter_analytics;
 pub use balances::map_balance_changes;
-use crate::pb::sf::jupiter::v1::BalanceChanges;
-
-#[substreams::handlers::map]
-pub fn db_out(changes: BalanceChanges) -> Result<DatabaseChanges, substreams::errors::Error> {
-    let mut tables = Tables::new();
-
-    for change in changes.params {
-        // "wallet_balance_changes" must match your ClickHouse CREATE TABLE name
-        let key = format!("{}:{}:{}", change.tx_id, change.owner, change.mint);
-
-        tables
-            .create_row("wallet_balance_changes", key)
-            .set("block_date", change.block_date)
-            .set("block_time", change.block_time)
-            .set("block_slot", change.block_slot)
-            .set("tx_id", change.tx_id)
-            .set("owner", change.owner)
-            .set("mint", change.mint)
-            .set("change_amount", change.change_amount)
-            .set("new_balance", change.new_balance)
-            .set("decimals", change.decimals)
-            .set("network_fee", change.network_fee.to_string());
-    }
-
-    Ok(tables.to_database_changes())
-}
\ No newline at end of file
+pub use classification::map_transaction_classifications;
+pub use trades::map_trades;
+pub use watchlist::{map_watchlist_candidates, store_watchlist};
+pub use transactions::map_transactions;
+pub use swap_routes::map_swap_routes;
+pub use order_events::{map_order_events, store_order_totals};
+pub use daily_totals::store_daily_totals;
+pub use graph_out::graph_out;
+pub use wallet_balances::{kv_out, store_account_balances, store_owner_balances};
+pub use files_out::files_out;
+pub use block_index::index_programs;
+pub use filtered_block::map_filtered_block;
+pub use db_out::db_out;
+pub use cost_basis::{
+    map_cost_basis_legs, map_realized_pnl, store_cost_basis_events, store_cost_basis_lows, store_cost_basis_totals,
+};
@@ -0,0 +1,252 @@
+use crate::instructions::{account_keys, ordered_instructions, read_u64, record_id, TxInstruction};
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{OrderEvent, OrderEvents};
+use crate::program_keys::{DCA_PROGRAM_KEY, LIMIT_ORDER_PROGRAM_KEY};
+use chrono::DateTime;
+use substreams::errors::Error;
+use substreams::scalar::BigInt;
+use substreams::store::{StoreAdd, StoreAddBigInt, StoreDelete, StoreNew};
+use substreams_solana::base58;
+
+// Anchor instruction discriminators (the first 8 bytes of sha256("global:<name>")), with
+// the positions of the order owner, when the instruction takes it, and of the order or
+// DCA account in the instruction's IDL accounts.
+const LIMIT_ORDER_INSTRUCTIONS: [([u8; 8], &str, Option<usize>, usize); 6] = [
+    ([133, 110, 74, 175, 112, 159, 245, 159], "initialize_order", Some(1), 2),
+    ([232, 122, 115, 25, 199, 143, 136, 162], "fill_order", Some(2), 0),
+    ([240, 47, 153, 68, 13, 190, 225, 42], "pre_flash_fill_order", None, 0),
+    ([252, 104, 18, 134, 164, 78, 18, 140], "flash_fill_order", Some(2), 0),
+    ([95, 129, 237, 240, 8, 49, 223, 132], "cancel_order", Some(2), 0),
+    ([216, 120, 64, 235, 155, 19, 229, 99], "cancel_expired_order", Some(2), 0),
+];
+
+const DCA_INSTRUCTIONS: [([u8; 8], &str, Option<usize>, usize); 10] = [
+    ([36, 65, 185, 54, 1, 210, 100, 163], "open_dca", Some(1), 0),
+    ([142, 119, 43, 109, 162, 52, 11, 177], "open_dca_v2", Some(1), 0),
+    ([22, 7, 33, 98, 168, 183, 34, 243], "close_dca", Some(0), 1),
+    ([83, 125, 166, 69, 247, 252, 103, 133], "end_and_close", Some(6), 1),
+    ([242, 35, 198, 137, 82, 225, 242, 182], "deposit", Some(0), 1),
+    ([183, 18, 70, 156, 148, 109, 161, 34], "withdraw", Some(0), 1),
+    // Keeper fills do not take the DCA owner.
+    ([143, 205, 3, 191, 162, 215, 245, 49], "initiate_flash_fill", None, 1),
+    ([115, 64, 226, 78, 33, 211, 105, 162], "fulfill_flash_fill", None, 1),
+    ([155, 193, 80, 121, 91, 147, 254, 187], "initiate_dlmm_fill", None, 1),
+    ([1, 230, 118, 251, 45, 177, 101, 187], "fulfill_dlmm_fill", None, 1),
+];
+
+// Prefix of the self-CPI Anchor uses to log events; these are not user actions.
+const ANCHOR_EVENT_CPI: [u8; 8] = [228, 69, 165, 46, 81, 203, 154, 29];
+
+/// One event per instruction of the Jupiter limit order and DCA programs, top-level or
+/// CPI. Amounts are decoded for order creation and DCA opening and deposits only; the
+/// owner is left empty for keeper fills and unknown instructions, which do not take it.
+#[substreams::handlers::map]
+pub fn map_order_events(block: FilteredBlock) -> Result<OrderEvents, Error> {
+    let timestamp = block.block_time.as_ref().map(|t| t.timestamp).unwrap_or(0);
+    let block_date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%d").to_string();
+    let mut events = Vec::new();
+
+    for (tx_index, trx) in block.indexed_transactions().filter(|(_, trx)| trx.is_successful()) {
+        let (meta, transaction) = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) => (meta, transaction),
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let instructions = ordered_instructions(message, meta);
+        let keys = account_keys(message, meta);
+
+        for inst in &instructions {
+            let (program, names) = match inst.program_id {
+                key if key == LIMIT_ORDER_PROGRAM_KEY => ("LIMIT_ORDER", &LIMIT_ORDER_INSTRUCTIONS[..]),
+                key if key == DCA_PROGRAM_KEY => ("DCA", &DCA_INSTRUCTIONS[..]),
+                _ => continue,
+            };
+            if inst.data.starts_with(&ANCHOR_EVENT_CPI) {
+                continue;
+            }
+            let (event, owner, order_account) = names
+                .iter()
+                .find(|(discriminator, ..)| inst.data.starts_with(discriminator))
+                .map(|(_, name, owner, order_account)| (*name, *owner, Some(*order_account)))
+                .unwrap_or(("unknown", None, None));
+            let amounts = decode_amounts(event, inst);
+            let account = |position: Option<usize>| {
+                position
+                    .and_then(|position| inst.account(position))
+                    .and_then(|index| keys.get(index))
+                    .map(base58::encode)
+                    .unwrap_or_default()
+            };
+            // A limit order account is closed by the fill that completes it.
+            let closed = order_account
+                .and_then(|position| inst.account(position))
+                .and_then(|index| meta.post_balances.get(index))
+                .is_some_and(|lamports| *lamports == 0);
+
+            events.push(OrderEvent {
+                block_date: block_date.clone(),
+                block_time: timestamp as u64,
+                block_slot: block.slot,
+                tx_id: tx_id.clone(),
+                program: program.to_string(),
+                program_id: base58::encode(inst.program_id),
+                event: event.to_string(),
+                user: account(owner),
+                order_account: account(order_account),
+                status: status(program, event, closed).to_string(),
+                accounts: inst
+                    .accounts
+                    .iter()
+                    .map(|index| keys.get(*index as usize).map(base58::encode).unwrap_or_default())
+                    .collect(),
+                in_amount: amounts.in_amount,
+                out_amount: amounts.out_amount,
+                in_amount_per_cycle: amounts.in_amount_per_cycle,
+                cycle_frequency: amounts.cycle_frequency,
+                tx_index,
+                instruction_index: inst.at.index,
+                inner_instruction_index: inst.at.inner_index,
+                stack_height: inst.stack_height,
+                id: record_id(block.slot, tx_index, inst.at),
+            });
+        }
+    }
+
+    Ok(OrderEvents { events })
+}
+
+#[derive(Default)]
+struct OrderAmounts {
+    in_amount: u64,
+    out_amount: u64,
+    in_amount_per_cycle: u64,
+    cycle_frequency: i64,
+}
+
+/// Reads the leading fixed-size arguments of the instructions whose layout is stable
+/// across program versions; everything else is left at zero.
+fn decode_amounts(event: &str, inst: &TxInstruction) -> OrderAmounts {
+    let arg = |offset: usize| read_u64(inst.data, 8 + offset).unwrap_or(0);
+    match event {
+        // making_amount: u64, taking_amount: u64, ...
+        "initialize_order" => OrderAmounts { in_amount: arg(0), out_amount: arg(8), ..Default::default() },
+        // making_amount: u64, max_taking_amount: u64; only the making side is exact.
+        "fill_order" | "pre_flash_fill_order" => OrderAmounts { in_amount: arg(0), ..Default::default() },
+        // repay_amount: u64, the output bought for the DCA
+        "fulfill_flash_fill" | "fulfill_dlmm_fill" => OrderAmounts { out_amount: arg(0), ..Default::default() },
+        // application_idx: u64, in_amount: u64, in_amount_per_cycle: u64, cycle_frequency: i64, ...
+        "open_dca" | "open_dca_v2" => OrderAmounts {
+            in_amount: arg(8),
+            in_amount_per_cycle: arg(16),
+            cycle_frequency: arg(24) as i64,
+            ..Default::default()
+        },
+        // deposit_in: u64
+        "deposit" => OrderAmounts { in_amount: arg(0), ..Default::default() },
+        _ => OrderAmounts::default(),
+    }
+}
+
+/// Status of the order or DCA position after the instruction, empty for unknown ones.
+fn status(program: &str, event: &str, closed: bool) -> &'static str {
+    match (program, event) {
+        (_, "unknown") => "",
+        ("LIMIT_ORDER", "initialize_order") => "OPEN",
+        ("LIMIT_ORDER", "cancel_order" | "cancel_expired_order") => "CANCELLED",
+        ("LIMIT_ORDER", _) if closed => "FILLED",
+        ("LIMIT_ORDER", _) => "PARTIALLY_FILLED",
+        (_, "close_dca") => "CLOSED",
+        (_, "end_and_close") => "COMPLETED",
+        _ => "OPEN",
+    }
+}
+
+/// Running totals per order or DCA account, keyed `<order_account>:<total>`:
+/// - `in`: DCA amount deposited, at opening and by later deposits.
+/// - `filled`: limit order making amount filled, or output a DCA bought.
+/// - `fills`: number of fills.
+///
+/// An account's totals are deleted once the order or position is closed.
+#[substreams::handlers::store]
+pub fn store_order_totals(orders: OrderEvents, store: StoreAddBigInt) {
+    for (ordinal, event) in orders.events.iter().enumerate().filter(|(_, event)| !event.order_account.is_empty()) {
+        let ordinal = ordinal as u64;
+        let key = |total: &str| format!("{}:{}", event.order_account, total);
+        match event.event.as_str() {
+            "open_dca" | "open_dca_v2" | "deposit" => store.add(ordinal, key("in"), BigInt::from(event.in_amount)),
+            "fill_order" | "pre_flash_fill_order" => {
+                store.add(ordinal, key("filled"), BigInt::from(event.in_amount));
+                store.add(ordinal, key("fills"), BigInt::one());
+            }
+            "fulfill_flash_fill" | "fulfill_dlmm_fill" => {
+                store.add(ordinal, key("filled"), BigInt::from(event.out_amount));
+                store.add(ordinal, key("fills"), BigInt::one());
+            }
+            _ => {}
+        }
+        if is_final(&event.status) {
+            store.delete_prefix(ordinal as i64, &format!("{}:", event.order_account));
+        }
+    }
+}
+
+pub(crate) fn is_final(status: &str) -> bool {
+    matches!(status, "FILLED" | "CANCELLED" | "CLOSED" | "COMPLETED")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::LIMIT_ORDER_PROGRAM_ID;
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, Message, Transaction, TransactionStatusMeta,
+    };
+
+    #[test]
+    fn fill_reports_the_maker_not_the_taker() {
+        // Taker (fee payer), order, reserve, maker, limit order program.
+        let account_keys = vec![
+            vec![1u8; 32],
+            vec![2u8; 32],
+            vec![3u8; 32],
+            vec![4u8; 32],
+            base58::decode(LIMIT_ORDER_PROGRAM_ID).unwrap(),
+        ];
+        let block = Block {
+            slot: 1,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        account_keys,
+                        instructions: vec![CompiledInstruction {
+                            program_id_index: 4,
+                            // order, reserve, maker, taker
+                            accounts: vec![1, 2, 3, 0],
+                            data: LIMIT_ORDER_INSTRUCTIONS[1].0.to_vec(),
+                        }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta::default()),
+            }],
+            ..Default::default()
+        };
+
+        let events = __impl_map_order_events(block.into()).unwrap().events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "fill_order");
+        assert_eq!(events[0].user, base58::encode([4u8; 32]));
+        assert_eq!(events[0].order_account, base58::encode([2u8; 32]));
+        // The order account is still open after the fill.
+        assert_eq!(events[0].status, "PARTIALLY_FILLED");
+    }
+}
@@ -17,6 +17,13 @@ pub mod jupiter {
 }
 pub mod sf {
     pub mod jupiter {
+        pub mod block {
+            // @@protoc_insertion_point(attribute:sf.jupiter.block.v1)
+            pub mod v1 {
+                include!("sf.jupiter.block.v1.rs");
+                // @@protoc_insertion_point(sf.jupiter.block.v1)
+            }
+        }
         // @@protoc_insertion_point(attribute:sf.jupiter.v1)
         pub mod v1 {
             include!("sf.jupiter.v1.rs");
@@ -42,11 +49,25 @@ pub mod sf {
             }
         }
         pub mod sink {
-            pub mod database {
-                // @@protoc_insertion_point(attribute:sf.substreams.sink.database.v1)
+            pub mod entity {
+                // @@protoc_insertion_point(attribute:sf.substreams.sink.entity.v1)
+                pub mod v1 {
+                    include!("sf.substreams.sink.entity.v1.rs");
+                    // @@protoc_insertion_point(sf.substreams.sink.entity.v1)
+                }
+            }
+            pub mod files {
+                // @@protoc_insertion_point(attribute:sf.substreams.sink.files.v1)
                 pub mod v1 {
-                    include!("sf.substreams.sink.database.v1.rs");
-                    // @@protoc_insertion_point(sf.substreams.sink.database.v1)
+                    include!("sf.substreams.sink.files.v1.rs");
+                    // @@protoc_insertion_point(sf.substreams.sink.files.v1)
+                }
+            }
+            pub mod kv {
+                // @@protoc_insertion_point(attribute:sf.substreams.sink.kv.v1)
+                pub mod v1 {
+                    include!("sf.substreams.sink.kv.v1.rs");
+                    // @@protoc_insertion_point(sf.substreams.sink.kv.v1)
                 }
             }
             pub mod service {
@@ -56,13 +77,6 @@ pub mod sf {
                     // @@protoc_insertion_point(sf.substreams.sink.service.v1)
                 }
             }
-            pub mod sql {
-                // @@protoc_insertion_point(attribute:sf.substreams.sink.sql.v1)
-                pub mod v1 {
-                    include!("sf.substreams.sink.sql.v1.rs");
-                    // @@protoc_insertion_point(sf.substreams.sink.sql.v1)
-                }
-            }
         }
         // @@protoc_insertion_point(attribute:sf.substreams.v1)
         pub mod v1 {
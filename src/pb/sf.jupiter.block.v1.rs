@@ -0,0 +1,26 @@
+// @generated
+// This file is @generated by prost-build.
+/// The transactions of a block that map_filtered_block keeps, with the block fields the
+/// modules reading it use.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilteredBlock {
+    #[prost(uint64, tag="1")]
+    pub slot: u64,
+    #[prost(string, tag="2")]
+    pub blockhash: ::prost::alloc::string::String,
+    #[prost(message, optional, tag="3")]
+    pub block_time: ::core::option::Option<::substreams_solana::pb::sf::solana::r#type::v1::UnixTimestamp>,
+    #[prost(message, repeated, tag="4")]
+    pub transactions: ::prost::alloc::vec::Vec<FilteredTransaction>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilteredTransaction {
+    /// Position of the transaction in the source block
+    #[prost(uint32, tag="1")]
+    pub tx_index: u32,
+    #[prost(message, optional, tag="2")]
+    pub transaction: ::core::option::Option<::substreams_solana::pb::sf::solana::r#type::v1::ConfirmedTransaction>,
+}
+// @@protoc_insertion_point(module)
@@ -2,6 +2,7 @@
 // This file is @generated by prost-build.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct TradingData {
     #[prost(string, tag="1")]
     pub program_id: ::prost::alloc::string::String,
@@ -15,9 +16,25 @@ pub struct TradingData {
     pub slot: u64,
     #[prost(uint64, tag="6")]
     pub block_time: u64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="7")]
+    pub tx_index: u32,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="8")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="9")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="10")]
+    pub stack_height: u32,
+    /// "<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>"
+    #[prost(string, tag="11")]
+    pub id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct TokenPrice {
     #[prost(string, tag="1")]
     pub mint_address: ::prost::alloc::string::String,
@@ -32,6 +49,7 @@ pub struct TokenPrice {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct EnrichedAccount {
     #[prost(string, tag="1")]
     pub address: ::prost::alloc::string::String,
@@ -42,6 +60,7 @@ pub struct EnrichedAccount {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct AccountOwnerRecord {
     #[prost(bytes="vec", tag="1")]
     pub account: ::prost::alloc::vec::Vec<u8>,
@@ -52,24 +71,28 @@ pub struct AccountOwnerRecord {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct AccountOwnerRecords {
     #[prost(message, repeated, tag="1")]
     pub records: ::prost::alloc::vec::Vec<AccountOwnerRecord>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct TradingDataList {
     #[prost(message, repeated, tag="1")]
     pub items: ::prost::alloc::vec::Vec<TradingData>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct TokenPriceList {
     #[prost(message, repeated, tag="1")]
     pub items: ::prost::alloc::vec::Vec<TokenPrice>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct JupiterInstruction {
     #[prost(string, tag="1")]
     pub program_id: ::prost::alloc::string::String,
@@ -83,15 +106,32 @@ pub struct JupiterInstruction {
     pub slot: u64,
     #[prost(uint64, tag="6")]
     pub block_time: u64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="7")]
+    pub tx_index: u32,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="8")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="9")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="10")]
+    pub stack_height: u32,
+    /// "<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>"
+    #[prost(string, tag="11")]
+    pub id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct JupiterInstructions {
     #[prost(message, repeated, tag="1")]
     pub instructions: ::prost::alloc::vec::Vec<JupiterInstruction>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct ProgramStat {
     #[prost(string, tag="1")]
     pub program_id: ::prost::alloc::string::String,
@@ -100,6 +140,7 @@ pub struct ProgramStat {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct JupiterAnalytics {
     #[prost(uint64, tag="1")]
     pub total_instructions: u64,
@@ -112,6 +153,7 @@ pub struct JupiterAnalytics {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct BalanceChange {
     #[prost(string, tag="1")]
     pub block_date: ::prost::alloc::string::String,
@@ -135,15 +177,546 @@ pub struct BalanceChange {
     pub new_balance: ::prost::alloc::string::String,
     #[prost(uint32, tag="9")]
     pub decimals: u32,
+    /// Label of the instruction that produced the change
     #[prost(string, tag="10")]
     pub change_type: ::prost::alloc::string::String,
     #[prost(double, tag="11")]
     pub network_fee: f64,
+    /// Label of the whole transaction
+    #[prost(string, tag="12")]
+    pub tx_change_type: ::prost::alloc::string::String,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="13")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="14")]
+    pub inner_instruction_index: i32,
+    /// Every label detected in the transaction
+    #[prost(string, repeated, tag="15")]
+    pub labels: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Label of the top-level program, empty if unclassified
+    #[prost(string, tag="16")]
+    pub invoker_label: ::prost::alloc::string::String,
+    /// Labels from the top-level program down, joined by ">"
+    #[prost(string, tag="17")]
+    pub cpi_path: ::prost::alloc::string::String,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="18")]
+    pub tx_index: u32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="19")]
+    pub stack_height: u32,
+    /// "<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>:<account_index>:<kind>", kind native, token, network_fee, rent_deposit or rent_refund
+    #[prost(string, tag="20")]
+    pub id: ::prost::alloc::string::String,
+    /// Account that moved: the token account, or the wallet for SOL
+    #[prost(string, tag="21")]
+    pub token_account: ::prost::alloc::string::String,
+    /// Index of token_account in the transaction's account keys
+    #[prost(uint32, tag="22")]
+    pub account_index: u32,
+    /// Lamport change rather than a token balance change
+    #[prost(bool, tag="23")]
+    pub native: bool,
+    /// What change_type's program does, e.g. "SWAP"; empty if unclassified
+    #[prost(string, tag="24")]
+    pub category: ::prost::alloc::string::String,
+    /// new_balance in raw units (lamports for SOL), as a u64 string
+    #[prost(string, tag="25")]
+    pub raw_new_balance: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
 pub struct BalanceChanges {
     #[prost(message, repeated, tag="1")]
     pub params: ::prost::alloc::vec::Vec<BalanceChange>,
 }
+/// Every protocol label detected in a transaction, not only the highest-priority one.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct TransactionClassification {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    /// Highest-priority label, as on BalanceChange.tx_change_type
+    #[prost(string, tag="5")]
+    pub change_type: ::prost::alloc::string::String,
+    /// Distinct labels in execution order
+    #[prost(string, repeated, tag="6")]
+    pub labels: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Highest-priority label among top-level programs
+    #[prost(string, tag="7")]
+    pub invoker_label: ::prost::alloc::string::String,
+    /// Distinct labelled CPI paths, e.g. "LEND_KAMINO>SWAP_JUPITER"
+    #[prost(string, repeated, tag="8")]
+    pub cpi_paths: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="9")]
+    pub tx_index: u32,
+    /// "<slot>:<tx_index>"
+    #[prost(string, tag="10")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct TransactionClassifications {
+    #[prost(message, repeated, tag="1")]
+    pub transactions: ::prost::alloc::vec::Vec<TransactionClassification>,
+}
+/// A single swap leg used for cost-basis accounting. Positive amounts are
+/// acquisitions, negative amounts are disposals.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct CostBasisLeg {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    #[prost(string, tag="5")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(string, tag="6")]
+    pub mint: ::prost::alloc::string::String,
+    #[prost(double, tag="7")]
+    pub amount: f64,
+    /// |amount| valued at the mint's USD price
+    #[prost(double, tag="8")]
+    pub usd_value: f64,
+    /// false when no USD price was known for the mint
+    #[prost(bool, tag="9")]
+    pub priced: bool,
+    #[prost(uint64, tag="10")]
+    pub ordinal: u64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="11")]
+    pub tx_index: u32,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="12")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="13")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="14")]
+    pub stack_height: u32,
+    /// Id of the BalanceChange it comes from
+    #[prost(string, tag="15")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct CostBasisLegs {
+    #[prost(message, repeated, tag="1")]
+    pub legs: ::prost::alloc::vec::Vec<CostBasisLeg>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct RealizedPnl {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    #[prost(string, tag="5")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(string, tag="6")]
+    pub mint: ::prost::alloc::string::String,
+    /// FIFO or AVERAGE
+    #[prost(string, tag="7")]
+    pub method: ::prost::alloc::string::String,
+    #[prost(double, tag="8")]
+    pub amount_disposed: f64,
+    /// Portion of the disposal covered by known lots
+    #[prost(double, tag="9")]
+    pub matched_amount: f64,
+    #[prost(double, tag="10")]
+    pub proceeds_usd: f64,
+    #[prost(double, tag="11")]
+    pub cost_basis_usd: f64,
+    #[prost(double, tag="12")]
+    pub realized_pnl_usd: f64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="13")]
+    pub tx_index: u32,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="14")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="15")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="16")]
+    pub stack_height: u32,
+    /// Id of the BalanceChange it comes from
+    #[prost(string, tag="17")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct RealizedPnls {
+    #[prost(message, repeated, tag="1")]
+    pub items: ::prost::alloc::vec::Vec<RealizedPnl>,
+}
+/// A signer's swap within one transaction, paired from its balance-change legs.
+/// Native SOL and wSOL legs are merged; network fee and rent rows are never part
+/// of a leg.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct Trade {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    #[prost(string, tag="5")]
+    pub trader: ::prost::alloc::string::String,
+    /// Label of the trader's first swap instruction
+    #[prost(string, tag="6")]
+    pub change_type: ::prost::alloc::string::String,
+    #[prost(string, tag="7")]
+    pub sold_mint: ::prost::alloc::string::String,
+    #[prost(double, tag="8")]
+    pub sold_amount: f64,
+    #[prost(string, tag="9")]
+    pub bought_mint: ::prost::alloc::string::String,
+    #[prost(double, tag="10")]
+    pub bought_amount: f64,
+    /// sold_amount per unit of bought_amount
+    #[prost(double, tag="11")]
+    pub effective_price: f64,
+    /// Only set when the trader paid the fee
+    #[prost(double, tag="12")]
+    pub network_fee: f64,
+    /// Trader's net rent flow in SOL, negative when rent was deposited
+    #[prost(double, tag="13")]
+    pub rent_adjustment: f64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="14")]
+    pub tx_index: u32,
+    /// Top-level instruction, -1 when unattributed
+    #[prost(int32, tag="15")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="16")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level, 0 when unattributed
+    #[prost(uint32, tag="17")]
+    pub stack_height: u32,
+    /// Id of the BalanceChange of the trader's first swap leg
+    #[prost(string, tag="18")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct Trades {
+    #[prost(message, repeated, tag="1")]
+    pub trades: ::prost::alloc::vec::Vec<Trade>,
+}
+/// A wallet that matched a watchlist rule in this block.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct WatchlistEntry {
+    #[prost(string, tag="1")]
+    pub address: ::prost::alloc::string::String,
+    /// e.g. "program:<id>" or "jupiter_usd:<amount>"
+    #[prost(string, tag="2")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag="5")]
+    pub ordinal: u64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="6")]
+    pub tx_index: u32,
+    /// "<slot>:<tx_index>"
+    #[prost(string, tag="7")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct WatchlistEntries {
+    #[prost(message, repeated, tag="1")]
+    pub entries: ::prost::alloc::vec::Vec<WatchlistEntry>,
+}
+/// One record per non-vote transaction; tx_id is the join key for the other tables.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct TransactionSummary {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    #[prost(string, tag="5")]
+    pub fee_payer: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag="6")]
+    pub signers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bool, tag="7")]
+    pub success: bool,
+    /// In SOL
+    #[prost(double, tag="8")]
+    pub fee: f64,
+    #[prost(uint64, tag="9")]
+    pub compute_units_consumed: u64,
+    /// Distinct program ids, top-level and CPI
+    #[prost(string, repeated, tag="10")]
+    pub programs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// "v6", "v4", "v3", "v2", "limit" or empty
+    #[prost(string, tag="11")]
+    pub jupiter_version: ::prost::alloc::string::String,
+    #[prost(string, tag="12")]
+    pub change_type: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag="13")]
+    pub labels: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, tag="14")]
+    pub invoker_label: ::prost::alloc::string::String,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="15")]
+    pub tx_index: u32,
+    /// "<slot>:<tx_index>"
+    #[prost(string, tag="16")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct TransactionSummaries {
+    #[prost(message, repeated, tag="1")]
+    pub transactions: ::prost::alloc::vec::Vec<TransactionSummary>,
+}
+/// One hop of a Jupiter route: a DEX program the aggregator called directly, with the
+/// first token transfer in its subtree as input and the last as output.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct SwapHop {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    /// Fee payer
+    #[prost(string, tag="5")]
+    pub trader: ::prost::alloc::string::String,
+    /// id of the Jupiter instruction owning the route
+    #[prost(string, tag="6")]
+    pub route_id: ::prost::alloc::string::String,
+    /// Position of the hop within its route
+    #[prost(uint32, tag="7")]
+    pub hop_index: u32,
+    #[prost(string, tag="8")]
+    pub program_id: ::prost::alloc::string::String,
+    /// Classification label of program_id, empty when unknown
+    #[prost(string, tag="9")]
+    pub dex: ::prost::alloc::string::String,
+    #[prost(string, tag="10")]
+    pub input_mint: ::prost::alloc::string::String,
+    /// Raw token units
+    #[prost(uint64, tag="11")]
+    pub input_amount: u64,
+    #[prost(string, tag="12")]
+    pub output_mint: ::prost::alloc::string::String,
+    /// Raw token units
+    #[prost(uint64, tag="13")]
+    pub output_amount: u64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="14")]
+    pub tx_index: u32,
+    /// Top-level instruction
+    #[prost(int32, tag="15")]
+    pub instruction_index: i32,
+    /// Inner instruction of the hop
+    #[prost(int32, tag="16")]
+    pub inner_instruction_index: i32,
+    /// CPI depth of the hop
+    #[prost(uint32, tag="17")]
+    pub stack_height: u32,
+    /// "<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>"
+    #[prost(string, tag="18")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct SwapHops {
+    #[prost(message, repeated, tag="1")]
+    pub hops: ::prost::alloc::vec::Vec<SwapHop>,
+}
+/// An instruction of the Jupiter limit order or DCA program.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct OrderEvent {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub tx_id: ::prost::alloc::string::String,
+    /// "LIMIT_ORDER" or "DCA"
+    #[prost(string, tag="5")]
+    pub program: ::prost::alloc::string::String,
+    #[prost(string, tag="6")]
+    pub program_id: ::prost::alloc::string::String,
+    /// Instruction name, e.g. "initialize_order", "open_dca_v2", or "unknown"
+    #[prost(string, tag="7")]
+    pub event: ::prost::alloc::string::String,
+    /// Order or DCA owner from the instruction accounts, empty for keeper fills
+    #[prost(string, tag="8")]
+    pub user: ::prost::alloc::string::String,
+    /// Instruction accounts, in order
+    #[prost(string, repeated, tag="9")]
+    pub accounts: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Limit order making amount (filled making amount for fills) or DCA deposit, 0 when not decoded
+    #[prost(uint64, tag="10")]
+    pub in_amount: u64,
+    /// Limit order taking amount or output bought by a DCA fill, 0 when not decoded
+    #[prost(uint64, tag="11")]
+    pub out_amount: u64,
+    /// DCA only
+    #[prost(uint64, tag="12")]
+    pub in_amount_per_cycle: u64,
+    /// DCA only, in seconds
+    #[prost(int64, tag="13")]
+    pub cycle_frequency: i64,
+    /// Position of the transaction in the block
+    #[prost(uint32, tag="14")]
+    pub tx_index: u32,
+    /// Top-level instruction
+    #[prost(int32, tag="15")]
+    pub instruction_index: i32,
+    /// Inner instruction, -1 for the top-level one
+    #[prost(int32, tag="16")]
+    pub inner_instruction_index: i32,
+    /// CPI depth, 1 for top-level
+    #[prost(uint32, tag="17")]
+    pub stack_height: u32,
+    /// "<slot>:<tx_index>:<instruction_index>:<inner_instruction_index>"
+    #[prost(string, tag="18")]
+    pub id: ::prost::alloc::string::String,
+    /// Limit order or DCA account, empty for unknown instructions
+    #[prost(string, tag="19")]
+    pub order_account: ::prost::alloc::string::String,
+    /// After the instruction: OPEN, PARTIALLY_FILLED, FILLED or CANCELLED for limit orders, OPEN, CLOSED or COMPLETED for DCA; empty for unknown instructions
+    #[prost(string, tag="20")]
+    pub status: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct OrderEvents {
+    #[prost(message, repeated, tag="1")]
+    pub events: ::prost::alloc::vec::Vec<OrderEvent>,
+}
+/// Last USD price of a mint in a block.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct TokenUsdPrice {
+    #[prost(string, tag="1")]
+    pub block_date: ::prost::alloc::string::String,
+    #[prost(uint64, tag="2")]
+    pub block_time: u64,
+    #[prost(uint64, tag="3")]
+    pub block_slot: u64,
+    #[prost(string, tag="4")]
+    pub mint: ::prost::alloc::string::String,
+    #[prost(double, tag="5")]
+    pub price_usd: f64,
+}
+/// OHLC state of a USD candle after a block that traded the mint.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct Candle {
+    #[prost(string, tag="1")]
+    pub mint: ::prost::alloc::string::String,
+    /// "1m", "1h" or "1d"
+    #[prost(string, tag="2")]
+    pub interval: ::prost::alloc::string::String,
+    /// Unix seconds
+    #[prost(uint64, tag="3")]
+    pub start_time: u64,
+    #[prost(double, tag="4")]
+    pub open: f64,
+    #[prost(double, tag="5")]
+    pub high: f64,
+    #[prost(double, tag="6")]
+    pub low: f64,
+    #[prost(double, tag="7")]
+    pub close: f64,
+    #[prost(double, tag="8")]
+    pub volume_usd: f64,
+    /// Last block that updated the candle
+    #[prost(uint64, tag="9")]
+    pub block_slot: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct PriceUpdates {
+    #[prost(message, repeated, tag="1")]
+    pub prices: ::prost::alloc::vec::Vec<TokenUsdPrice>,
+    #[prost(message, repeated, tag="2")]
+    pub candles: ::prost::alloc::vec::Vec<Candle>,
+}
+/// Current balance of an owner for a mint, the value of kv_out's "<owner>:<mint>" keys.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(serde::Serialize)]
+pub struct WalletBalance {
+    #[prost(string, tag="1")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub mint: ::prost::alloc::string::String,
+    /// Sum over the owner's token accounts, in raw units
+    #[prost(string, tag="3")]
+    pub raw_balance: ::prost::alloc::string::String,
+    #[prost(uint32, tag="4")]
+    pub decimals: u32,
+    /// Last slot that changed the balance
+    #[prost(uint64, tag="5")]
+    pub block_slot: u64,
+    #[prost(uint64, tag="6")]
+    pub block_time: u64,
+    /// Last transaction that changed the balance
+    #[prost(string, tag="7")]
+    pub tx_id: ::prost::alloc::string::String,
+}
 // @@protoc_insertion_point(module)
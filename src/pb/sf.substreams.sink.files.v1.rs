@@ -0,0 +1,10 @@
+// @generated
+// This file is @generated by prost-build.
+/// Lines written as-is, one per line, by the files sink's lines encoder.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Lines {
+    #[prost(string, repeated, tag="1")]
+    pub lines: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+// @@protoc_insertion_point(module)
@@ -0,0 +1,54 @@
+// @generated
+// This file is @generated by prost-build.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvOperations {
+    #[prost(message, repeated, tag="1")]
+    pub operations: ::prost::alloc::vec::Vec<KvOperation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvOperation {
+    #[prost(string, tag="1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(bytes="vec", tag="2")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag="3")]
+    pub ordinal: u64,
+    #[prost(enumeration="kv_operation::Type", tag="4")]
+    pub r#type: i32,
+}
+/// Nested message and enum types in `KVOperation`.
+pub mod kv_operation {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Type {
+        /// Protobuf default should not be used, this is used so that the consume can ensure that the value was actually specified
+        Unset = 0,
+        Set = 1,
+        Delete = 2,
+    }
+    impl Type {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Type::Unset => "UNSET",
+                Type::Set => "SET",
+                Type::Delete => "DELETE",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "UNSET" => Some(Self::Unset),
+                "SET" => Some(Self::Set),
+                "DELETE" => Some(Self::Delete),
+                _ => None,
+            }
+        }
+    }
+}
+// @@protoc_insertion_point(module)
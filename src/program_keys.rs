@@ -0,0 +1,146 @@
+use crate::constants::{
+    ASSOCIATED_TOKEN_PROGRAM_ID, COMPUTE_BUDGET_PROGRAM_ID, DCA_PROGRAM_ID, JUPITER_PROGRAM_IDS,
+    JUPITER_PROGRAM_VERSIONS, LIMIT_ORDER_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+    VOTE_PROGRAM_ID,
+};
+
+// Raw 32-byte program keys, decoded from the base58 constants at compile time, so hot
+// loops compare account keys as bytes and only encode the programs they emit.
+
+/// `JUPITER_PROGRAM_IDS` as raw keys, sorted for `is_jupiter_key`.
+pub(crate) const JUPITER_PROGRAM_KEYS: [[u8; 32]; JUPITER_PROGRAM_IDS.len()] = sorted(decode_all(JUPITER_PROGRAM_IDS));
+pub(crate) const LIMIT_ORDER_PROGRAM_KEY: [u8; 32] = program_key(LIMIT_ORDER_PROGRAM_ID);
+pub(crate) const DCA_PROGRAM_KEY: [u8; 32] = program_key(DCA_PROGRAM_ID);
+pub(crate) const VOTE_PROGRAM_KEY: [u8; 32] = program_key(VOTE_PROGRAM_ID);
+pub(crate) const SYSTEM_PROGRAM_KEY: [u8; 32] = program_key(SYSTEM_PROGRAM_ID);
+pub(crate) const TOKEN_PROGRAM_KEY: [u8; 32] = program_key(TOKEN_PROGRAM_ID);
+pub(crate) const TOKEN_2022_PROGRAM_KEY: [u8; 32] = program_key(TOKEN_2022_PROGRAM_ID);
+pub(crate) const ASSOCIATED_TOKEN_PROGRAM_KEY: [u8; 32] = program_key(ASSOCIATED_TOKEN_PROGRAM_ID);
+pub(crate) const COMPUTE_BUDGET_PROGRAM_KEY: [u8; 32] = program_key(COMPUTE_BUDGET_PROGRAM_ID);
+
+/// `JUPITER_PROGRAM_IDS` as raw keys, in the order of `JUPITER_PROGRAM_VERSIONS`.
+const JUPITER_VERSION_KEYS: [[u8; 32]; JUPITER_PROGRAM_IDS.len()] = decode_all(JUPITER_PROGRAM_IDS);
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Whether a raw account key is one of the Jupiter programs.
+pub(crate) fn is_jupiter_key(key: &[u8]) -> bool {
+    JUPITER_PROGRAM_KEYS.binary_search_by(|probe| probe.as_slice().cmp(key)).is_ok()
+}
+
+/// Version name of a Jupiter program key, e.g. `v6`.
+pub(crate) fn jupiter_version(key: &[u8]) -> Option<&'static str> {
+    JUPITER_VERSION_KEYS
+        .iter()
+        .zip(JUPITER_PROGRAM_VERSIONS)
+        .find(|(program, _)| program.as_slice() == key)
+        .map(|(_, version)| version)
+}
+
+/// Decodes a base58 address; anything but a 32-byte address fails the build when used
+/// in a constant.
+pub(crate) const fn program_key(address: &str) -> [u8; 32] {
+    let chars = address.as_bytes();
+    let mut key = [0u8; 32];
+
+    let mut i = 0;
+    while i < chars.len() {
+        let mut carry = digit(chars[i]) as u32;
+        let mut j = 32;
+        while j > 0 {
+            j -= 1;
+            carry += key[j] as u32 * 58;
+            key[j] = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        if carry != 0 {
+            panic!("base58 address is longer than 32 bytes");
+        }
+        i += 1;
+    }
+
+    // Each leading '1' stands for one leading zero byte.
+    let mut ones = 0;
+    while ones < chars.len() && chars[ones] == b'1' {
+        ones += 1;
+    }
+    let mut zeros = 0;
+    while zeros < 32 && key[zeros] == 0 {
+        zeros += 1;
+    }
+    if ones != zeros {
+        panic!("base58 address is not 32 bytes");
+    }
+    key
+}
+
+const fn digit(c: u8) -> u8 {
+    let mut value = 0;
+    while value < BASE58_ALPHABET.len() {
+        if BASE58_ALPHABET[value] == c {
+            return value as u8;
+        }
+        value += 1;
+    }
+    panic!("invalid base58 character")
+}
+
+const fn decode_all<const N: usize>(addresses: [&str; N]) -> [[u8; 32]; N] {
+    let mut keys = [[0u8; 32]; N];
+    let mut i = 0;
+    while i < N {
+        keys[i] = program_key(addresses[i]);
+        i += 1;
+    }
+    keys
+}
+
+const fn sorted<const N: usize>(mut keys: [[u8; 32]; N]) -> [[u8; 32]; N] {
+    let mut i = 1;
+    while i < N {
+        let mut j = i;
+        while j > 0 && less(&keys[j], &keys[j - 1]) {
+            let previous = keys[j - 1];
+            keys[j - 1] = keys[j];
+            keys[j] = previous;
+            j -= 1;
+        }
+        i += 1;
+    }
+    keys
+}
+
+const fn less(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let mut i = 0;
+    while i < 32 {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+        i += 1;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use substreams_solana::base58;
+
+    #[test]
+    fn decodes_like_base58() {
+        for address in JUPITER_PROGRAM_IDS
+            .into_iter()
+            .chain([LIMIT_ORDER_PROGRAM_ID, DCA_PROGRAM_ID, VOTE_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID])
+            .chain([TOKEN_2022_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID, COMPUTE_BUDGET_PROGRAM_ID])
+        {
+            assert_eq!(program_key(address).to_vec(), base58::decode(address).unwrap(), "{}", address);
+        }
+        for address in JUPITER_PROGRAM_IDS {
+            assert!(is_jupiter_key(&program_key(address)));
+        }
+        assert!(!is_jupiter_key(&program_key(TOKEN_PROGRAM_ID)));
+        assert!(!is_jupiter_key(&[0u8; 31]));
+        assert_eq!(jupiter_version(&program_key(JUPITER_PROGRAM_IDS[0])), Some(JUPITER_PROGRAM_VERSIONS[0]));
+        assert_eq!(jupiter_version(&TOKEN_PROGRAM_KEY), None);
+    }
+}
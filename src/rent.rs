@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::constants::WRAPPED_SOL_MINT;
+use crate::instructions::{
+    read_u32, read_u64, InstructionRef, TxInstruction, SYSTEM_CREATE_ACCOUNT, SYSTEM_CREATE_ACCOUNT_WITH_SEED,
+    SYSTEM_TRANSFER, TOKEN_CLOSE_ACCOUNT,
+};
+use crate::program_keys::ASSOCIATED_TOKEN_PROGRAM_KEY;
+use substreams_solana::pb::sf::solana::r#type::v1::TransactionStatusMeta;
+
+/// Lamports moved by creating and closing accounts within one transaction, keyed by
+/// account index, with the last instruction that moved them. Deposits are negative for
+/// the funder and positive for the new account; refunds are negative for the closed
+/// account and positive for the destination.
+#[derive(Default)]
+pub(crate) struct RentFlows {
+    pub deposits: HashMap<usize, (i128, InstructionRef)>,
+    pub refunds: HashMap<usize, (i128, InstructionRef)>,
+}
+
+impl RentFlows {
+    pub fn deposit(&self, index: usize) -> (i128, InstructionRef) {
+        self.deposits.get(&index).copied().unwrap_or((0, InstructionRef::NONE))
+    }
+
+    pub fn refund(&self, index: usize) -> (i128, InstructionRef) {
+        self.refunds.get(&index).copied().unwrap_or((0, InstructionRef::NONE))
+    }
+}
+
+/// Collects rent flows from System `CreateAccount`/`CreateAccountWithSeed`, from the
+/// System transfer the associated token account program makes to top up a pre-funded
+/// address it creates, and from SPL Token `CloseAccount`. A deposit into a token account
+/// that is still open is capped at its rent-exempt reserve, so lamports wrapped into a
+/// new wSOL account stay a transfer. For a closed wSOL account only the lamports above
+/// its wrapped balance are rent; the wrapped balance is an unwrap, not a refund.
+pub(crate) fn rent_flows(instructions: &[TxInstruction], meta: &TransactionStatusMeta) -> RentFlows {
+    let mut flows = RentFlows::default();
+
+    let pre_token_accounts: HashSet<u32> = meta.pre_token_balances.iter().map(|b| b.account_index).collect();
+    let created_token_accounts: HashSet<usize> = meta
+        .post_token_balances
+        .iter()
+        .filter(|b| !pre_token_accounts.contains(&b.account_index))
+        .map(|b| b.account_index as usize)
+        .collect();
+
+    // Program of each instruction up the CPI stack of the current one.
+    let mut callers: Vec<&[u8]> = Vec::new();
+    for inst in instructions {
+        callers.truncate(inst.stack_height.saturating_sub(1) as usize);
+        let caller = callers.last().copied();
+        callers.push(inst.program_id);
+
+        if inst.is_system() {
+            let (from, to) = match (inst.account(0), inst.account(1)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+            let lamports = match read_u32(inst.data, 0) {
+                Some(SYSTEM_CREATE_ACCOUNT) => read_u64(inst.data, 4),
+                Some(SYSTEM_TRANSFER)
+                    if caller == Some(&ASSOCIATED_TOKEN_PROGRAM_KEY[..]) && created_token_accounts.contains(&to) =>
+                {
+                    read_u64(inst.data, 4)
+                }
+                Some(SYSTEM_CREATE_ACCOUNT_WITH_SEED) => read_u64(inst.data, 36)
+                    .and_then(|seed_len| read_u64(inst.data, 44 + seed_len as usize)),
+                _ => None,
+            };
+            let lamports = match (lamports, rent_reserve(meta, to)) {
+                (Some(lamports), Some(reserve)) => Some((lamports as i128).min(reserve - flows.deposit(to).0)),
+                (lamports, None) => lamports.map(|lamports| lamports as i128),
+                (None, _) => None,
+            };
+            if let Some(lamports) = lamports.filter(|lamports| *lamports > 0) {
+                add(&mut flows.deposits, from, -lamports, inst.at);
+                add(&mut flows.deposits, to, lamports, inst.at);
+            }
+        } else if inst.is_token() {
+            if inst.data.first() != Some(&TOKEN_CLOSE_ACCOUNT) {
+                continue;
+            }
+            let (closed, destination) = match (inst.account(0), inst.account(1)) {
+                (Some(closed), Some(destination)) => (closed, destination),
+                _ => continue,
+            };
+            // Accounts created earlier in the same transaction hold the rent deposited then.
+            let lamports = meta.pre_balances.get(closed).copied().unwrap_or(0) as i128 + flows.deposit(closed).0.max(0);
+            let wrapped = meta
+                .pre_token_balances
+                .iter()
+                .find(|b| b.account_index as usize == closed && b.mint == WRAPPED_SOL_MINT)
+                .and_then(|b| b.ui_token_amount.as_ref())
+                .and_then(|a| a.amount.parse::<i128>().ok())
+                .unwrap_or(0);
+            let refund = (lamports - wrapped).max(0);
+            add(&mut flows.refunds, closed, -refund, inst.at);
+            add(&mut flows.refunds, destination, refund, inst.at);
+        }
+    }
+
+    flows
+}
+
+/// Lamports a token account still open after the transaction holds beyond its wrapped
+/// balance, i.e. its rent-exempt reserve. `None` for other accounts.
+fn rent_reserve(meta: &TransactionStatusMeta, index: usize) -> Option<i128> {
+    let balance = meta.post_token_balances.iter().find(|b| b.account_index as usize == index)?;
+    let lamports = *meta.post_balances.get(index)? as i128;
+    let wrapped = match balance.mint == WRAPPED_SOL_MINT {
+        true => balance.ui_token_amount.as_ref().and_then(|a| a.amount.parse::<i128>().ok()).unwrap_or(0),
+        false => 0,
+    };
+    Some(lamports - wrapped)
+}
+
+/// Returns true when a System instruction moves lamports that `rent_flows` counts as rent.
+pub(crate) fn is_rent_transfer(inst: &TxInstruction, flows: &RentFlows) -> bool {
+    inst.account(1)
+        .and_then(|to| flows.deposits.get(&to))
+        .map(|(_, at)| *at == inst.at)
+        .unwrap_or(false)
+}
+
+fn add(flows: &mut HashMap<usize, (i128, InstructionRef)>, index: usize, lamports: i128, at: InstructionRef) {
+    let entry = flows.entry(index).or_insert((0, at));
+    entry.0 += lamports;
+    entry.1 = at;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::balances::__impl_map_balance_changes;
+    use crate::constants::{ASSOCIATED_TOKEN_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID, WRAPPED_SOL_MINT};
+    use substreams::store::{StoreGet, StoreGetString};
+    use substreams_solana::base58;
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message, MessageHeader,
+        TokenBalance, Transaction, TransactionStatusMeta, UiTokenAmount,
+    };
+
+    const RENT: u64 = 2_039_280;
+    const WRAPPED: u64 = 5_000_000_000;
+
+    fn system_data(discriminator: u32, lamports: u64) -> Vec<u8> {
+        [discriminator.to_le_bytes().as_slice(), lamports.to_le_bytes().as_slice()].concat()
+    }
+
+    /// The fee payer creates its wSOL associated token account, transfers 5 SOL into it
+    /// and syncs it, keeping the account open.
+    fn wrap_into_new_ata() -> Block {
+        let payer = vec![1u8; 32];
+        let account_keys = vec![
+            payer.clone(),
+            vec![2u8; 32],
+            base58::decode(SYSTEM_PROGRAM_ID).unwrap(),
+            base58::decode(TOKEN_PROGRAM_ID).unwrap(),
+            base58::decode(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap(),
+            base58::decode(WRAPPED_SOL_MINT).unwrap(),
+        ];
+        let instruction = |program_id_index: u32, accounts: Vec<u8>, data: Vec<u8>| CompiledInstruction {
+            program_id_index,
+            accounts,
+            data,
+        };
+        let inner = |program_id_index: u32, accounts: Vec<u8>, data: Vec<u8>| InnerInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height: Some(2),
+        };
+
+        Block {
+            slot: 1,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys,
+                        instructions: vec![
+                            // CreateIdempotent
+                            instruction(4, vec![0, 1, 0, 5, 2, 3], vec![1]),
+                            instruction(2, vec![0, 1], system_data(2, WRAPPED)),
+                            // SyncNative
+                            instruction(3, vec![1], vec![17]),
+                        ],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    fee: 5_000,
+                    pre_balances: vec![10_000_000_000, 0, 1, 1, 1, 1],
+                    post_balances: vec![10_000_000_000 - 5_000 - RENT - WRAPPED, RENT + WRAPPED, 1, 1, 1, 1],
+                    inner_instructions: vec![InnerInstructions {
+                        index: 0,
+                        instructions: vec![
+                            inner(2, vec![0, 1], [system_data(0, RENT), 165u64.to_le_bytes().to_vec(), vec![6u8; 32]].concat()),
+                            // InitializeAccount3
+                            inner(3, vec![1, 5], [vec![18], payer.clone()].concat()),
+                        ],
+                    }],
+                    post_token_balances: vec![TokenBalance {
+                        account_index: 1,
+                        mint: WRAPPED_SOL_MINT.to_string(),
+                        owner: base58::encode(&payer),
+                        ui_token_amount: Some(UiTokenAmount {
+                            ui_amount: 5.0,
+                            decimals: 9,
+                            amount: WRAPPED.to_string(),
+                            ui_amount_string: "5".to_string(),
+                        }),
+                        program_id: TOKEN_PROGRAM_ID.to_string(),
+                    }],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn rows(params: &str) -> Vec<(String, String, String)> {
+        __impl_map_balance_changes(params.to_string(), wrap_into_new_ata().into(), StoreGetString::new(0))
+            .unwrap()
+            .params
+            .into_iter()
+            .map(|change| (change.token_account, change.change_type, change.change_amount))
+            .collect()
+    }
+
+    #[test]
+    fn wrapping_into_a_new_ata_deposits_only_the_reserve() {
+        let payer = base58::encode([1u8; 32]);
+        let ata = base58::encode([2u8; 32]);
+        let row = |account: &str, change_type: &str, amount: &str| (account.to_string(), change_type.to_string(), amount.to_string());
+
+        // The 5 SOL stays a transfer between the payer and the wSOL account.
+        assert_eq!(
+            rows("sol=raw"),
+            vec![
+                row(&payer, "NETWORK_FEE", "-0.000005"),
+                row(&payer, "RENT_DEPOSIT", "-0.00203928"),
+                row(&payer, "SEND", "-5"),
+                row(&ata, "RENT_DEPOSIT", "0.00203928"),
+                row(&ata, "SEND", "5"),
+                row(&ata, "SEND", "5"),
+            ]
+        );
+        // Netted with the wrapped balance, only the fee and the reserve leave the payer.
+        assert_eq!(rows(""), vec![row(&payer, "NETWORK_FEE", "-0.000005"), row(&payer, "RENT_DEPOSIT", "-0.00203928")]);
+    }
+}
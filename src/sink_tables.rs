@@ -0,0 +1,93 @@
+use substreams::errors::Error;
+
+/// Every table `db_out` can write, in the order it writes them.
+pub(crate) const SINK_TABLES: [&str; 11] = [
+    "wallet_balance_changes",
+    "transactions",
+    "swaps",
+    "swap_routes",
+    "limit_orders",
+    "dca_events",
+    "token_prices",
+    "candles",
+    "daily_mint_volumes",
+    "daily_wallet_flows",
+    "daily_fees",
+];
+
+/// Parsed `db_out` params selecting the tables to write, e.g.
+/// `tables=swaps,swap_routes` or `exclude_tables=candles`. Values are comma-separated
+/// table names from `SINK_TABLES`; empty params write every table.
+pub(crate) struct SinkTables {
+    enabled: [bool; SINK_TABLES.len()],
+}
+
+impl SinkTables {
+    pub fn parse(params: &str) -> Result<Self, Error> {
+        let mut included: Option<Vec<&str>> = None;
+        let mut excluded: Vec<&str> = Vec::new();
+
+        for segment in params.split('&').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = match segment.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(anyhow::anyhow!("db_out param '{}' is not key=value", segment)),
+            };
+            let names = value.split(',').map(str::trim).filter(|s| !s.is_empty());
+            let target = match key {
+                "tables" => included.get_or_insert_with(Vec::new),
+                "exclude_tables" => &mut excluded,
+                _ => return Err(anyhow::anyhow!("unknown db_out param '{}'", key)),
+            };
+            for name in names {
+                if !SINK_TABLES.contains(&name) {
+                    return Err(anyhow::anyhow!(
+                        "unknown db_out table '{}', expected one of {}",
+                        name,
+                        SINK_TABLES.join(", ")
+                    ));
+                }
+                target.push(name);
+            }
+        }
+
+        let mut enabled = [false; SINK_TABLES.len()];
+        for (slot, table) in enabled.iter_mut().zip(SINK_TABLES) {
+            *slot = included.as_ref().is_none_or(|names| names.contains(&table)) && !excluded.contains(&table);
+        }
+        Ok(SinkTables { enabled })
+    }
+
+    pub fn includes(&self, table: &str) -> bool {
+        SINK_TABLES
+            .iter()
+            .zip(self.enabled)
+            .any(|(name, enabled)| enabled && *name == table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_params_write_every_table() {
+        let tables = SinkTables::parse("").unwrap();
+        assert!(SINK_TABLES.iter().all(|table| tables.includes(table)));
+    }
+
+    #[test]
+    fn selects_and_excludes_tables() {
+        let tables = SinkTables::parse("tables=swaps,candles,transactions&exclude_tables=candles").unwrap();
+        assert!(tables.includes("swaps"));
+        assert!(tables.includes("transactions"));
+        assert!(!tables.includes("candles"));
+        assert!(!tables.includes("wallet_balance_changes"));
+    }
+
+    #[test]
+    fn rejects_unknown_tables_and_keys() {
+        assert!(SinkTables::parse("tables=swap").is_err());
+        assert!(SinkTables::parse("table=swaps").is_err());
+        assert!(SinkTables::parse("swaps").is_err());
+    }
+}
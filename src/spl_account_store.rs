@@ -1,5 +1,5 @@
-use crate::constants::TOKEN_PROGRAM_ID;
 use crate::pb::sf::jupiter::v1::{AccountOwnerRecord, AccountOwnerRecords};
+use crate::program_keys::TOKEN_PROGRAM_KEY;
 use substreams::errors::Error;
 use substreams_solana::pb::sf::solana::r#type::v1::Block;
 
@@ -9,7 +9,7 @@ pub fn map_spl_initialized_account(block: Block) -> Result<AccountOwnerRecords,
 
     for trx in block.transactions() {
         for instruction in trx.walk_instructions() {
-            if instruction.program_id().to_string() != TOKEN_PROGRAM_ID {
+            if instruction.program_id().as_ref() != TOKEN_PROGRAM_KEY {
                 continue;
             }
 
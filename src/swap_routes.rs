@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::classification::Classifier;
+use crate::instructions::{
+    account_keys, ordered_instructions, read_u64, record_id, TxInstruction, TOKEN_TRANSFER, TOKEN_TRANSFER_CHECKED,
+};
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{SwapHop, SwapHops};
+use crate::program_keys::{
+    jupiter_version, ASSOCIATED_TOKEN_PROGRAM_KEY, COMPUTE_BUDGET_PROGRAM_KEY, SYSTEM_PROGRAM_KEY,
+    TOKEN_2022_PROGRAM_KEY, TOKEN_PROGRAM_KEY,
+};
+use chrono::DateTime;
+use substreams::errors::Error;
+use substreams_solana::base58;
+use substreams_solana::pb::sf::solana::r#type::v1::TransactionStatusMeta;
+
+/// One row per DEX hop of every Jupiter aggregator route. Params take the same
+/// `label.*` and `priority.*` classification rules as `map_balance_changes`, which
+/// name the `dex` of each hop.
+#[substreams::handlers::map]
+pub fn map_swap_routes(params: String, block: FilteredBlock) -> Result<SwapHops, Error> {
+    let classifier = Classifier::from_params(&params)?;
+    let timestamp = block.block_time.as_ref().map(|t| t.timestamp).unwrap_or(0);
+    let block_date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%d").to_string();
+    let mut hops = Vec::new();
+
+    for (tx_index, trx) in block.indexed_transactions().filter(|(_, trx)| trx.is_successful()) {
+        let (meta, transaction) = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) => (meta, transaction),
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let instructions = ordered_instructions(message, meta);
+        if !instructions.iter().any(|inst| is_aggregator(inst.program_id)) {
+            continue;
+        }
+        let keys = account_keys(message, meta);
+        let mints = token_account_mints(meta);
+        // TransferChecked names its mint; plain transfers go through the token balances.
+        let mint_of = |inst: &TxInstruction, position: usize| -> String {
+            match inst.data.first() {
+                Some(&TOKEN_TRANSFER_CHECKED) => inst.account(1).and_then(|mint| keys.get(mint)).map(base58::encode),
+                _ => inst.account(position).and_then(|account| mints.get(&account).cloned()),
+            }
+            .unwrap_or_default()
+        };
+        let trader = message.account_keys.first().map(base58::encode).unwrap_or_default();
+
+        for (position, route) in instructions.iter().enumerate() {
+            if !is_aggregator(route.program_id) {
+                continue;
+            }
+            let route_id = record_id(block.slot, tx_index, route.at);
+            let calls = subtree(&instructions, position);
+            let mut hop_index = 0;
+
+            for (offset, hop) in calls.iter().enumerate() {
+                if hop.stack_height != route.stack_height + 1 || !is_dex_candidate(hop.program_id) {
+                    continue;
+                }
+                let transfers: Vec<&TxInstruction> = subtree(calls, offset)
+                    .iter()
+                    .filter(|inst| inst.is_token() && transfer_amount(inst).is_some())
+                    .collect();
+                let (input, output) = match (transfers.first(), transfers.last()) {
+                    (Some(input), Some(output)) if transfers.len() >= 2 => (*input, *output),
+                    _ => continue,
+                };
+
+                hops.push(SwapHop {
+                    block_date: block_date.clone(),
+                    block_time: timestamp as u64,
+                    block_slot: block.slot,
+                    tx_id: tx_id.clone(),
+                    trader: trader.clone(),
+                    route_id: route_id.clone(),
+                    hop_index,
+                    program_id: base58::encode(hop.program_id),
+                    dex: classifier.classify(hop.program_id).map(|(label, _)| label.to_string()).unwrap_or_default(),
+                    input_mint: mint_of(input, 0),
+                    input_amount: transfer_amount(input).unwrap_or(0),
+                    output_mint: mint_of(output, destination_position(output)),
+                    output_amount: transfer_amount(output).unwrap_or(0),
+                    tx_index,
+                    instruction_index: hop.at.index,
+                    inner_instruction_index: hop.at.inner_index,
+                    stack_height: hop.stack_height,
+                    id: record_id(block.slot, tx_index, hop.at),
+                });
+                hop_index += 1;
+            }
+        }
+    }
+
+    Ok(SwapHops { hops })
+}
+
+fn is_aggregator(program_id: &[u8]) -> bool {
+    jupiter_version(program_id).is_some_and(|version| version != "limit")
+}
+
+/// Programs a route calls that are plumbing rather than a venue.
+fn is_dex_candidate(program_id: &[u8]) -> bool {
+    !is_aggregator(program_id)
+        && program_id != ASSOCIATED_TOKEN_PROGRAM_KEY
+        && program_id != COMPUTE_BUDGET_PROGRAM_KEY
+        && program_id != SYSTEM_PROGRAM_KEY
+        && program_id != TOKEN_PROGRAM_KEY
+        && program_id != TOKEN_2022_PROGRAM_KEY
+}
+
+/// The CPIs made by the instruction at `position`, directly or not.
+fn subtree<'a, 'b>(instructions: &'a [TxInstruction<'b>], position: usize) -> &'a [TxInstruction<'b>] {
+    let parent = &instructions[position];
+    let rest = &instructions[position + 1..];
+    let end = rest
+        .iter()
+        .position(|inst| inst.at.index != parent.at.index || inst.stack_height <= parent.stack_height)
+        .unwrap_or(rest.len());
+    &rest[..end]
+}
+
+fn transfer_amount(inst: &TxInstruction) -> Option<u64> {
+    match inst.data.first() {
+        Some(&TOKEN_TRANSFER) | Some(&TOKEN_TRANSFER_CHECKED) => read_u64(inst.data, 1),
+        _ => None,
+    }
+}
+
+fn destination_position(inst: &TxInstruction) -> usize {
+    match inst.data.first() {
+        Some(&TOKEN_TRANSFER_CHECKED) => 2,
+        _ => 1,
+    }
+}
+
+/// Mint of every token account with a recorded balance, by account index.
+fn token_account_mints(meta: &TransactionStatusMeta) -> HashMap<usize, String> {
+    meta.pre_token_balances
+        .iter()
+        .chain(meta.post_token_balances.iter())
+        .map(|balance| (balance.account_index as usize, balance.mint.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{JUPITER_PROGRAM_IDS, TOKEN_PROGRAM_ID};
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message, TokenBalance,
+        Transaction,
+    };
+
+    const RAYDIUM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+
+    fn transfer(amount: u64, from: u8, to: u8) -> InnerInstruction {
+        let mut data = vec![TOKEN_TRANSFER];
+        data.extend_from_slice(&amount.to_le_bytes());
+        InnerInstruction { program_id_index: 5, accounts: vec![from, to, 0], data, stack_height: Some(3) }
+    }
+
+    fn balance(account_index: u32, mint: &str) -> TokenBalance {
+        TokenBalance { account_index, mint: mint.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn decodes_one_hop_per_dex_call() {
+        // Keys: trader, user USDC, pool USDC, pool wSOL, user wSOL, token program,
+        // Jupiter v6, Raydium.
+        let mut account_keys: Vec<Vec<u8>> = (1..=5u8).map(|i| vec![i; 32]).collect();
+        account_keys.push(base58::decode(TOKEN_PROGRAM_ID).unwrap());
+        account_keys.push(base58::decode(JUPITER_PROGRAM_IDS[0]).unwrap());
+        account_keys.push(base58::decode(RAYDIUM).unwrap());
+
+        let block = Block {
+            slot: 7,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        account_keys,
+                        instructions: vec![CompiledInstruction { program_id_index: 6, ..Default::default() }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    inner_instructions: vec![InnerInstructions {
+                        index: 0,
+                        instructions: vec![
+                            InnerInstruction { program_id_index: 7, stack_height: Some(2), ..Default::default() },
+                            transfer(1_000_000, 1, 2),
+                            transfer(5_000_000, 3, 4),
+                        ],
+                    }],
+                    pre_token_balances: vec![balance(1, USDC), balance(2, USDC), balance(3, WSOL), balance(4, WSOL)],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        };
+
+        let hops = __impl_map_swap_routes(String::new(), block.into()).unwrap().hops;
+        assert_eq!(hops.len(), 1);
+        let hop = &hops[0];
+        assert_eq!(hop.program_id, RAYDIUM);
+        assert_eq!(hop.dex, "SWAP_RAYDIUM");
+        assert_eq!((hop.input_mint.as_str(), hop.input_amount), (USDC, 1_000_000));
+        assert_eq!((hop.output_mint.as_str(), hop.output_amount), (WSOL, 5_000_000));
+        assert_eq!(hop.route_id, "7:0:0:-1");
+        assert_eq!(hop.id, "7:0:0:0");
+    }
+}
@@ -1,7 +1,19 @@
 use std::collections::HashSet;
 
-use crate::pb::sf::jupiter::v1::{TokenPrice, TokenPriceList, TradingDataList};
+use crate::cost_basis::{is_usd_stable, swap_legs_by_owner, SwapLeg};
+use crate::pb::sf::jupiter::v1::{
+    BalanceChanges, Candle, PriceUpdates, TokenPrice, TokenPriceList, TokenUsdPrice, TradingDataList,
+};
+use chrono::DateTime;
 use substreams::errors::Error;
+use substreams::pb::substreams::Clock;
+use substreams::store::{
+    DeltaFloat64, Deltas, StoreAdd, StoreAddFloat64, StoreDelete, StoreGet, StoreGetFloat64, StoreMax, StoreMaxFloat64,
+    StoreMin, StoreMinFloat64, StoreNew, StoreSet, StoreSetFloat64, StoreSetIfNotExists, StoreSetIfNotExistsFloat64,
+};
+
+/// Candle intervals and their length in seconds.
+const CANDLE_INTERVALS: [(&str, u64); 3] = [("1m", 60), ("1h", 3_600), ("1d", 86_400)];
 
 #[substreams::handlers::map]
 pub fn map_token_prices(trading_data: TradingDataList) -> Result<TokenPriceList, Error> {
@@ -24,3 +36,187 @@ pub fn map_token_prices(trading_data: TradingDataList) -> Result<TokenPriceList,
 
     Ok(TokenPriceList { items: prices })
 }
+
+/// A non-stable swap leg priced by the stablecoins flowing the other way in the same
+/// transaction, for owners whose only non-stable leg was traded against USDC/USDT.
+pub(crate) struct SwapPrice<'a> {
+    pub leg: &'a SwapLeg<'a>,
+    pub price: f64,
+    pub usd_volume: f64,
+}
+
+pub(crate) fn swap_prices<'a>(groups: &'a [Vec<SwapLeg<'a>>]) -> Vec<SwapPrice<'a>> {
+    let mut prices = Vec::new();
+
+    for group in groups {
+        let (stable, other): (Vec<_>, Vec<_>) = group.iter().partition(|leg| is_usd_stable(&leg.change.mint));
+        if stable.is_empty() || other.len() != 1 {
+            continue;
+        }
+
+        let leg = other[0];
+        // Stable flow in the opposite direction of the priced leg.
+        let stable_amount: f64 = stable
+            .iter()
+            .filter(|s| s.amount.signum() != leg.amount.signum())
+            .map(|s| s.amount.abs())
+            .sum();
+        if stable_amount <= 0.0 || leg.amount == 0.0 {
+            continue;
+        }
+
+        prices.push(SwapPrice { leg, price: stable_amount / leg.amount.abs(), usd_volume: stable_amount });
+    }
+
+    prices
+}
+
+/// Latest USD price per mint. Keys are `usd:<mint>`.
+#[substreams::handlers::store]
+pub fn store_token_usd_prices(changes: BalanceChanges, store: StoreSetFloat64) {
+    let groups = swap_legs_by_owner(&changes);
+    for price in swap_prices(&groups) {
+        store.set(price.leg.ordinal, format!("usd:{}", price.leg.change.mint), &price.price);
+    }
+}
+
+/// First price of each candle, keyed by `candle_key`. Candle stores only hold open
+/// candles: `map_price_updates` emits every candle as it changes, so closed ones are
+/// deleted.
+#[substreams::handlers::store]
+pub fn store_candle_opens(clock: Clock, changes: BalanceChanges, store: StoreSetIfNotExistsFloat64) {
+    delete_closed_candles(&clock, &store);
+    let groups = swap_legs_by_owner(&changes);
+    for price in swap_prices(&groups) {
+        for key in candle_keys(&price) {
+            store.set_if_not_exists(price.leg.ordinal, key, &price.price);
+        }
+    }
+}
+
+#[substreams::handlers::store]
+pub fn store_candle_highs(clock: Clock, changes: BalanceChanges, store: StoreMaxFloat64) {
+    delete_closed_candles(&clock, &store);
+    let groups = swap_legs_by_owner(&changes);
+    for price in swap_prices(&groups) {
+        for key in candle_keys(&price) {
+            store.max(price.leg.ordinal, key, price.price);
+        }
+    }
+}
+
+#[substreams::handlers::store]
+pub fn store_candle_lows(clock: Clock, changes: BalanceChanges, store: StoreMinFloat64) {
+    delete_closed_candles(&clock, &store);
+    let groups = swap_legs_by_owner(&changes);
+    for price in swap_prices(&groups) {
+        for key in candle_keys(&price) {
+            store.min(price.leg.ordinal, key, price.price);
+        }
+    }
+}
+
+/// USD traded per candle, measured on the stablecoin side.
+#[substreams::handlers::store]
+pub fn store_candle_volumes(clock: Clock, changes: BalanceChanges, store: StoreAddFloat64) {
+    delete_closed_candles(&clock, &store);
+    let groups = swap_legs_by_owner(&changes);
+    for price in swap_prices(&groups) {
+        for key in candle_keys(&price) {
+            store.add(price.leg.ordinal, key, price.usd_volume);
+        }
+    }
+}
+
+/// The last USD price of every mint priced in this block, and the state of each of its
+/// candles after the block. The close is the block's last price.
+#[substreams::handlers::map]
+pub fn map_price_updates(
+    clock: Clock,
+    prices: Deltas<DeltaFloat64>,
+    opens: StoreGetFloat64,
+    highs: StoreGetFloat64,
+    lows: StoreGetFloat64,
+    volumes: StoreGetFloat64,
+) -> Result<PriceUpdates, Error> {
+    let timestamp = clock.timestamp.as_ref().map(|t| t.seconds).unwrap_or(0);
+    let block_date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%d").to_string();
+
+    // A mint can be priced several times in a block; keep its last price.
+    let mut closes: Vec<(&str, f64)> = Vec::new();
+    for delta in prices.deltas.iter() {
+        let mint = match delta.key.strip_prefix("usd:") {
+            Some(mint) => mint,
+            None => continue,
+        };
+        match closes.iter_mut().find(|(seen, _)| *seen == mint) {
+            Some((_, close)) => *close = delta.new_value,
+            None => closes.push((mint, delta.new_value)),
+        }
+    }
+
+    let mut updates = PriceUpdates::default();
+    for (mint, close) in closes {
+        updates.prices.push(TokenUsdPrice {
+            block_date: block_date.clone(),
+            block_time: timestamp as u64,
+            block_slot: clock.number,
+            mint: mint.to_string(),
+            price_usd: close,
+        });
+
+        for (interval, seconds) in CANDLE_INTERVALS {
+            let start_time = candle_start(timestamp as u64, seconds);
+            let key = candle_key(interval, mint, start_time);
+            updates.candles.push(Candle {
+                mint: mint.to_string(),
+                interval: interval.to_string(),
+                start_time,
+                open: opens.get_last(&key).unwrap_or(close),
+                high: highs.get_last(&key).unwrap_or(close),
+                low: lows.get_last(&key).unwrap_or(close),
+                close,
+                volume_usd: volumes.get_last(&key).unwrap_or(0.0),
+                block_slot: clock.number,
+            });
+        }
+    }
+
+    Ok(updates)
+}
+
+/// Deletes the two candles of each interval before the block's one. Every block deletes
+/// them, so a candle with no block of its own still gets its predecessor deleted.
+fn delete_closed_candles(clock: &Clock, store: &impl StoreDelete) {
+    let timestamp = clock.timestamp.as_ref().map(|t| t.seconds).unwrap_or(0) as u64;
+    for (interval, seconds) in CANDLE_INTERVALS {
+        let start_time = candle_start(timestamp, seconds);
+        for closed in 1..=2 {
+            if let Some(closed_start) = start_time.checked_sub(closed * seconds) {
+                store.delete_prefix(0, &candle_prefix(interval, closed_start));
+            }
+        }
+    }
+}
+
+fn candle_keys(price: &SwapPrice) -> Vec<String> {
+    CANDLE_INTERVALS
+        .iter()
+        .map(|(interval, seconds)| {
+            candle_key(interval, &price.leg.change.mint, candle_start(price.leg.change.block_time, *seconds))
+        })
+        .collect()
+}
+
+fn candle_start(timestamp: u64, seconds: u64) -> u64 {
+    timestamp - timestamp % seconds
+}
+
+fn candle_key(interval: &str, mint: &str, start_time: u64) -> String {
+    format!("{}{}", candle_prefix(interval, start_time), mint)
+}
+
+/// Keys of every mint's candle of one interval starting at `start_time`.
+fn candle_prefix(interval: &str, start_time: u64) -> String {
+    format!("candle:{}:{}:", interval, start_time)
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use crate::balances::{NETWORK_FEE, RENT_DEPOSIT, RENT_REFUND};
+use crate::classification::Category;
+use crate::constants::WRAPPED_SOL_MINT;
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{BalanceChange, BalanceChanges, Trade, Trades};
+use substreams::errors::Error;
+use substreams_solana::base58;
+
+// Legs smaller than this (in UI units) are treated as rounding noise.
+const LEG_EPSILON: f64 = 1e-9;
+
+#[substreams::handlers::map]
+pub fn map_trades(changes: BalanceChanges, block: FilteredBlock) -> Result<Trades, Error> {
+    let signers_by_tx = signers_by_tx(&block);
+    let mut trades = Vec::new();
+
+    let mut by_tx: Vec<(&str, Vec<&BalanceChange>)> = Vec::new();
+    for change in changes.params.iter() {
+        match by_tx.last_mut() {
+            Some((tx_id, rows)) if *tx_id == change.tx_id => rows.push(change),
+            _ => by_tx.push((change.tx_id.as_str(), vec![change])),
+        }
+    }
+
+    for (tx_id, rows) in by_tx {
+        let signers = match signers_by_tx.get(tx_id) {
+            Some(signers) => signers,
+            None => continue,
+        };
+        for signer in signers {
+            // The signer's first swap row locates the trade and names it.
+            let origin = match rows.iter().find(|row| &row.owner == signer && is_swap(row)) {
+                Some(origin) => *origin,
+                None => continue,
+            };
+
+            // Net the legs of swap instructions per mint; native SOL and wSOL share
+            // WRAPPED_SOL_MINT so a wrap or unwrap cancels out here. Transfers and other
+            // instructions in the same transaction are not part of the trade. Fee and rent
+            // rows are already split out by map_balance_changes and only summed for reporting.
+            let mut legs: Vec<(String, f64)> = Vec::new();
+            let mut network_fee = 0.0;
+            let mut rent_adjustment = 0.0;
+            for row in rows.iter().filter(|row| &row.owner == signer) {
+                let amount: f64 = match row.change_amount.parse() {
+                    Ok(amount) => amount,
+                    Err(_) => continue,
+                };
+                if row.change_type == NETWORK_FEE {
+                    network_fee -= amount;
+                    continue;
+                }
+                if row.change_type == RENT_DEPOSIT || row.change_type == RENT_REFUND {
+                    rent_adjustment += amount;
+                    continue;
+                }
+                if !is_swap(row) {
+                    continue;
+                }
+                match legs.iter_mut().find(|(mint, _)| mint == &row.mint) {
+                    Some((_, total)) => *total += amount,
+                    None => legs.push((row.mint.clone(), amount)),
+                }
+            }
+
+            let sold = pick_leg(legs.iter().filter(|(_, amount)| *amount < -LEG_EPSILON));
+            let bought = pick_leg(legs.iter().filter(|(_, amount)| *amount > LEG_EPSILON));
+            let ((sold_mint, sold_amount), (bought_mint, bought_amount)) = match (sold, bought) {
+                (Some(sold), Some(bought)) => (sold, bought),
+                _ => continue,
+            };
+
+            trades.push(Trade {
+                block_date: origin.block_date.clone(),
+                block_time: origin.block_time,
+                block_slot: origin.block_slot,
+                tx_id: tx_id.to_string(),
+                trader: signer.clone(),
+                change_type: origin.change_type.clone(),
+                sold_mint: sold_mint.clone(),
+                sold_amount: -sold_amount,
+                bought_mint: bought_mint.clone(),
+                bought_amount: *bought_amount,
+                effective_price: -sold_amount / bought_amount,
+                network_fee,
+                rent_adjustment,
+                tx_index: origin.tx_index,
+                instruction_index: origin.instruction_index,
+                inner_instruction_index: origin.inner_instruction_index,
+                stack_height: origin.stack_height,
+                id: origin.id.clone(),
+            });
+        }
+    }
+
+    Ok(Trades { trades })
+}
+
+/// Whether the row was moved by a swap-category instruction.
+fn is_swap(row: &BalanceChange) -> bool {
+    row.category == Category::Swap.as_str()
+}
+
+/// Picks the trade leg from one side of a signer's legs. A leftover SOL leg next to a
+/// token leg is usually a tip or priority payment, so token legs win; among several
+/// token legs the largest one is taken.
+fn pick_leg<'a>(side: impl Iterator<Item = &'a (String, f64)>) -> Option<&'a (String, f64)> {
+    let side: Vec<_> = side.collect();
+    let tokens: Vec<_> = side.iter().filter(|(mint, _)| mint != WRAPPED_SOL_MINT).collect();
+    match tokens.len() {
+        0 => side.first().copied(),
+        _ => tokens
+            .into_iter()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .copied(),
+    }
+}
+
+/// Signer addresses of every successful transaction, keyed by signature. `BalanceChanges`
+/// does not say which owners signed.
+fn signers_by_tx(block: &FilteredBlock) -> HashMap<String, Vec<String>> {
+    let mut signers_by_tx = HashMap::new();
+
+    for (_, trx) in block.indexed_transactions() {
+        let transaction = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) if meta.err.is_none() => transaction,
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let num_signers = message
+            .header
+            .as_ref()
+            .map(|h| h.num_required_signatures as usize)
+            .unwrap_or(1);
+        let signers: Vec<String> = message
+            .account_keys
+            .iter()
+            .take(num_signers)
+            .map(base58::encode)
+            .collect();
+        signers_by_tx.insert(tx_id, signers);
+    }
+
+    signers_by_tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balances::__impl_map_balance_changes;
+    use crate::constants::{JUPITER_V6_PROGRAM_ID, TOKEN_PROGRAM_ID};
+    use crate::instructions::TOKEN_TRANSFER;
+    use substreams::store::{StoreGet, StoreGetString};
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, InnerInstruction, InnerInstructions, Message, MessageHeader,
+        TokenBalance, Transaction, TransactionStatusMeta, UiTokenAmount,
+    };
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+
+    fn row(owner: &str, mint: &str, amount: &str, change_type: &str, category: &str, instruction_index: i32) -> BalanceChange {
+        BalanceChange {
+            tx_id: base58::encode([9u8; 64]),
+            owner: owner.to_string(),
+            mint: mint.to_string(),
+            change_amount: amount.to_string(),
+            change_type: change_type.to_string(),
+            category: category.to_string(),
+            instruction_index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn trade_legs_come_from_swap_instructions_only() {
+        let signer = base58::encode([1u8; 32]);
+        let block = Block {
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys: vec![vec![1u8; 32]],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta::default()),
+            }],
+            ..Default::default()
+        };
+        // A transfer of 5 USDC to another wallet precedes a 20 USDC -> BONK swap.
+        let changes = BalanceChanges {
+            params: vec![
+                row(&signer, WRAPPED_SOL_MINT, "-0.000005", "NETWORK_FEE", "", -1),
+                row(&signer, USDC, "-5", "SEND", "", 0),
+                row(&signer, USDC, "-20", "SWAP_JUPITER", "SWAP", 1),
+                row(&signer, BONK, "1000", "SWAP_JUPITER", "SWAP", 1),
+            ],
+        };
+
+        let trades = __impl_map_trades(changes, block.into()).unwrap().trades;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].change_type, "SWAP_JUPITER");
+        assert_eq!((trades[0].sold_mint.as_str(), trades[0].sold_amount), (USDC, 20.0));
+        assert_eq!((trades[0].bought_mint.as_str(), trades[0].bought_amount), (BONK, 1000.0));
+        assert_eq!(trades[0].instruction_index, 1);
+    }
+
+    #[test]
+    fn jupiter_swap_inside_a_lending_instruction_is_a_trade() {
+        let signer = base58::encode([1u8; 32]);
+        let account_keys = vec![
+            vec![1u8; 32],
+            vec![2u8; 32],
+            vec![3u8; 32],
+            vec![4u8; 32],
+            vec![5u8; 32],
+            vec![6u8; 32],
+            base58::decode("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD").unwrap(),
+            base58::decode(JUPITER_V6_PROGRAM_ID).unwrap(),
+            base58::decode(TOKEN_PROGRAM_ID).unwrap(),
+        ];
+        let inner = |program_id_index: u32, accounts: Vec<u8>, data: Vec<u8>, stack_height: u32| InnerInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height: Some(stack_height),
+        };
+        let transfer = |amount: u64| [vec![TOKEN_TRANSFER], amount.to_le_bytes().to_vec()].concat();
+        let balance = |account_index: u32, mint: &str, amount: u64| TokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            owner: signer.clone(),
+            ui_token_amount: Some(UiTokenAmount {
+                ui_amount: amount as f64,
+                decimals: 0,
+                amount: amount.to_string(),
+                ui_amount_string: amount.to_string(),
+            }),
+            program_id: TOKEN_PROGRAM_ID.to_string(),
+        };
+        // Kamino calls Jupiter to swap 20 USDC for 1000 BONK, then takes a 5 USDC deposit.
+        let block = Block {
+            slot: 1,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys,
+                        instructions: vec![CompiledInstruction { program_id_index: 6, accounts: vec![0, 1, 4, 5], data: vec![] }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    fee: 5_000,
+                    pre_balances: vec![1_000_000_000, 1, 1, 1, 1, 1, 1, 1, 1],
+                    post_balances: vec![999_995_000, 1, 1, 1, 1, 1, 1, 1, 1],
+                    inner_instructions: vec![InnerInstructions {
+                        index: 0,
+                        instructions: vec![
+                            inner(7, vec![0, 1, 4], vec![], 2),
+                            inner(8, vec![1, 2, 0], transfer(20), 3),
+                            inner(8, vec![3, 4, 2], transfer(1000), 3),
+                            inner(8, vec![1, 5, 0], transfer(5), 2),
+                        ],
+                    }],
+                    pre_token_balances: vec![balance(1, USDC, 100), balance(4, BONK, 0)],
+                    post_token_balances: vec![balance(1, USDC, 75), balance(4, BONK, 1000)],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        };
+
+        let changes = __impl_map_balance_changes(String::new(), block.clone().into(), StoreGetString::new(0)).unwrap();
+        let categories: Vec<_> = changes
+            .params
+            .iter()
+            .filter(|change| !change.native)
+            .map(|change| (change.change_amount.as_str(), change.change_type.as_str(), change.category.as_str()))
+            .collect();
+        assert_eq!(
+            categories,
+            vec![("-20", "LEND_KAMINO", "SWAP"), ("-5", "LEND_KAMINO", "LEND"), ("1000", "LEND_KAMINO", "SWAP")]
+        );
+
+        let trades = __impl_map_trades(changes, block.into()).unwrap().trades;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].trader, signer);
+        assert_eq!((trades[0].sold_mint.as_str(), trades[0].sold_amount), (USDC, 20.0));
+        assert_eq!((trades[0].bought_mint.as_str(), trades[0].bought_amount), (BONK, 1000.0));
+    }
+}
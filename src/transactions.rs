@@ -0,0 +1,74 @@
+use crate::classification::{Classifier, TxClassification};
+use crate::constants::{JUPITER_PROGRAM_VERSIONS, LAMPORTS_PER_SOL};
+use crate::instructions::{ordered_instructions, tx_record_id};
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{TransactionSummaries, TransactionSummary};
+use crate::program_keys::jupiter_version;
+use chrono::DateTime;
+use substreams::errors::Error;
+use substreams_solana::base58;
+
+/// One summary per transaction of the filtered block, which already left out votes,
+/// failed ones included. Params take the same `label.*` and `priority.*` classification
+/// rules as `map_balance_changes`.
+#[substreams::handlers::map]
+pub fn map_transactions(params: String, block: FilteredBlock) -> Result<TransactionSummaries, Error> {
+    let classifier = Classifier::from_params(&params)?;
+    let timestamp = block.block_time.as_ref().map(|t| t.timestamp).unwrap_or(0);
+    let block_date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%d").to_string();
+    let mut transactions = Vec::new();
+
+    for (tx_index, trx) in block.indexed_transactions() {
+        let (meta, transaction) = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) => (meta, transaction),
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let instructions = ordered_instructions(message, meta);
+
+        let mut programs: Vec<&[u8]> = Vec::new();
+        for inst in &instructions {
+            if !programs.contains(&inst.program_id) {
+                programs.push(inst.program_id);
+            }
+        }
+        let jupiter_version = JUPITER_PROGRAM_VERSIONS
+            .into_iter()
+            .find(|version| programs.iter().any(|program| jupiter_version(program) == Some(*version)))
+            .unwrap_or_default()
+            .to_string();
+
+        let num_signers = message.header.as_ref().map(|h| h.num_required_signatures as usize).unwrap_or(1);
+        let signers: Vec<String> = message.account_keys.iter().take(num_signers).map(base58::encode).collect();
+        let classification = TxClassification::new(&classifier, &instructions);
+
+        transactions.push(TransactionSummary {
+            block_date: block_date.clone(),
+            block_time: timestamp as u64,
+            block_slot: block.slot,
+            tx_id,
+            fee_payer: signers.first().cloned().unwrap_or_default(),
+            signers,
+            success: meta.err.is_none(),
+            fee: meta.fee as f64 / LAMPORTS_PER_SOL,
+            compute_units_consumed: meta.compute_units_consumed.unwrap_or(0),
+            programs: programs.into_iter().map(base58::encode).collect(),
+            jupiter_version,
+            change_type: classification.change_type.clone(),
+            labels: classification.labels.iter().map(|label| label.to_string()).collect(),
+            invoker_label: classification.invoker_label.to_string(),
+            tx_index,
+            id: tx_record_id(block.slot, tx_index),
+        });
+    }
+
+    Ok(TransactionSummaries { transactions })
+}
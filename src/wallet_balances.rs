@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::pb::sf::jupiter::v1::{BalanceChange, BalanceChanges, WalletBalance};
+use crate::pb::sf::substreams::sink::kv::v1::kv_operation::Type;
+use crate::pb::sf::substreams::sink::kv::v1::{KvOperation, KvOperations};
+use prost::Message;
+use substreams::errors::Error;
+use substreams::scalar::BigInt;
+use substreams::store::{DeltaBigInt, Deltas, StoreAdd, StoreAddBigInt, StoreNew, StoreSet, StoreSetBigInt};
+
+/// Latest raw balance of every account, keyed `<owner>:<mint>:<token_account>:<native|token>`.
+/// Rows of `aggregate=owner` that merged several token accounts name none and are skipped.
+#[substreams::handlers::store]
+pub fn store_account_balances(changes: BalanceChanges, store: StoreSetBigInt) {
+    for (ordinal, change) in changes.params.iter().enumerate() {
+        if change.token_account.is_empty() {
+            continue;
+        }
+        if let Ok(raw) = BigInt::from_str(&change.raw_new_balance) {
+            store.set(ordinal as u64, account_key(change), &raw);
+        }
+    }
+}
+
+/// Owner totals per mint, keyed `<owner>:<mint>`, kept by adding how much each account's
+/// balance moved. An account seen for the first time adds its whole balance.
+#[substreams::handlers::store]
+pub fn store_owner_balances(accounts: Deltas<DeltaBigInt>, store: StoreAddBigInt) {
+    for delta in accounts.deltas {
+        let mut parts = delta.key.splitn(3, ':');
+        let (owner, mint) = match (parts.next(), parts.next()) {
+            (Some(owner), Some(mint)) => (owner, mint),
+            _ => continue,
+        };
+        store.add(delta.ordinal, format!("{}:{}", owner, mint), delta.new_value - delta.old_value);
+    }
+}
+
+/// One KV operation per `<owner>:<mint>` whose balance changed in the block: a `SET` of an
+/// encoded `WalletBalance`, or a `DELETE` once the balance is back to zero.
+#[substreams::handlers::map]
+pub fn kv_out(changes: BalanceChanges, owners: Deltas<DeltaBigInt>) -> Result<KvOperations, Error> {
+    let mut latest: HashMap<(&str, &str), &BalanceChange> = HashMap::new();
+    for change in &changes.params {
+        latest.insert((change.owner.as_str(), change.mint.as_str()), change);
+    }
+
+    // Keep the last delta of each key, in first-seen order.
+    let mut balances: Vec<&DeltaBigInt> = Vec::new();
+    for delta in owners.deltas.iter() {
+        match balances.iter_mut().find(|seen| seen.key == delta.key) {
+            Some(seen) => *seen = delta,
+            None => balances.push(delta),
+        }
+    }
+
+    let mut operations = Vec::new();
+    for delta in balances {
+        let (owner, mint) = match delta.key.split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        if delta.new_value.is_zero() {
+            operations.push(KvOperation {
+                key: delta.key.clone(),
+                value: Vec::new(),
+                ordinal: delta.ordinal,
+                r#type: Type::Delete as i32,
+            });
+            continue;
+        }
+
+        let change = latest.get(&(owner, mint));
+        let value = WalletBalance {
+            owner: owner.to_string(),
+            mint: mint.to_string(),
+            raw_balance: delta.new_value.to_string(),
+            decimals: change.map(|c| c.decimals).unwrap_or(0),
+            block_slot: change.map(|c| c.block_slot).unwrap_or(0),
+            block_time: change.map(|c| c.block_time).unwrap_or(0),
+            tx_id: change.map(|c| c.tx_id.clone()).unwrap_or_default(),
+        };
+        operations.push(KvOperation {
+            key: delta.key.clone(),
+            value: value.encode_to_vec(),
+            ordinal: delta.ordinal,
+            r#type: Type::Set as i32,
+        });
+    }
+
+    Ok(KvOperations { operations })
+}
+
+fn account_key(change: &BalanceChange) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        change.owner,
+        change.mint,
+        change.token_account,
+        if change.native { "native" } else { "token" }
+    )
+}
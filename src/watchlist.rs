@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use crate::balances::raw_amount;
+use crate::cost_basis::is_usd_stable;
+use crate::instructions::{ordered_instructions, tx_record_id};
+use crate::pb::sf::jupiter::block::v1::FilteredBlock;
+use crate::pb::sf::jupiter::v1::{WatchlistEntries, WatchlistEntry};
+use crate::program_keys::JUPITER_PROGRAM_KEYS;
+use substreams::errors::Error;
+use substreams::store::{StoreGet, StoreGetString, StoreNew, StoreSetIfNotExists, StoreSetIfNotExistsString};
+use substreams_solana::base58;
+use substreams_solana::pb::sf::solana::r#type::v1::TransactionStatusMeta;
+
+/// Rules that add wallets to the watchlist, from `map_watchlist_candidates` params:
+/// - `program=<id>[,<id>]`: signers of any transaction invoking the program, directly or
+///   through a CPI.
+/// - `jupiter_usd=<X>`: signers of a Jupiter transaction that paid or received at least X
+///   of their USDC/USDT. Other legs have no USD price at this point in the graph.
+///
+/// Rules read the block directly so the watchlist can feed `map_balance_changes` without
+/// depending on it.
+#[derive(Default)]
+struct WatchlistRules {
+    programs: Vec<[u8; 32]>,
+    jupiter_usd: Option<f64>,
+}
+
+impl WatchlistRules {
+    fn parse(params: &str) -> Result<Self, Error> {
+        let mut rules = WatchlistRules::default();
+
+        for segment in params.split('&').map(str::trim).filter(|s| !s.is_empty()) {
+            match segment.split_once('=') {
+                Some(("program", value)) => {
+                    for program in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                        match base58::decode(program).ok().and_then(|bytes| bytes.try_into().ok()) {
+                            Some(key) => rules.programs.push(key),
+                            None => return Err(anyhow::anyhow!("invalid watchlist program id '{}'", program)),
+                        }
+                    }
+                }
+                Some(("jupiter_usd", value)) => match value.parse::<f64>() {
+                    Ok(usd) if usd.is_finite() && usd >= 0.0 => rules.jupiter_usd = Some(usd),
+                    _ => return Err(anyhow::anyhow!("invalid watchlist jupiter_usd '{}'", value)),
+                },
+                _ => return Err(anyhow::anyhow!("unknown watchlist rule '{}'", segment)),
+            }
+        }
+
+        Ok(rules)
+    }
+}
+
+#[substreams::handlers::map]
+pub fn map_watchlist_candidates(params: String, block: FilteredBlock) -> Result<WatchlistEntries, Error> {
+    let rules = WatchlistRules::parse(&params)?;
+    let mut entries = Vec::new();
+
+    if rules.programs.is_empty() && rules.jupiter_usd.is_none() {
+        return Ok(WatchlistEntries { entries });
+    }
+
+    for (tx_index, trx) in block.indexed_transactions() {
+        let (meta, transaction) = match (&trx.meta, &trx.transaction) {
+            (Some(meta), Some(transaction)) if meta.err.is_none() => (meta, transaction),
+            _ => continue,
+        };
+        let message = match &transaction.message {
+            Some(message) => message,
+            None => continue,
+        };
+        let tx_id = match transaction.signatures.first() {
+            Some(signature) => base58::encode(signature),
+            None => continue,
+        };
+
+        let num_signers = message.header.as_ref().map(|h| h.num_required_signatures as usize).unwrap_or(1);
+        let signers: Vec<String> = message.account_keys.iter().take(num_signers).map(base58::encode).collect();
+        let instructions = ordered_instructions(message, meta);
+        let invokes = |program: &[u8]| instructions.iter().any(|inst| inst.program_id == program);
+
+        let mut reasons: Vec<(String, String)> = Vec::new();
+
+        for program in rules.programs.iter().filter(|program| invokes(program.as_slice())) {
+            for signer in &signers {
+                reasons.push((signer.clone(), format!("program:{}", base58::encode(program))));
+            }
+        }
+
+        if let Some(min_usd) = rules.jupiter_usd {
+            if JUPITER_PROGRAM_KEYS.iter().any(|program| invokes(program)) {
+                for (signer, usd) in stable_flows(meta, &signers) {
+                    if usd >= min_usd {
+                        reasons.push((signer, format!("jupiter_usd:{}", usd)));
+                    }
+                }
+            }
+        }
+
+        for (address, reason) in reasons {
+            entries.push(WatchlistEntry {
+                address,
+                reason,
+                block_slot: block.slot,
+                tx_id: tx_id.clone(),
+                ordinal: entries.len() as u64,
+                tx_index,
+                id: tx_record_id(block.slot, tx_index),
+            });
+        }
+    }
+
+    Ok(WatchlistEntries { entries })
+}
+
+/// Wallets to track, keyed `wallet:<address>`. The first reason a wallet qualified is kept.
+#[substreams::handlers::store]
+pub fn store_watchlist(candidates: WatchlistEntries, store: StoreSetIfNotExistsString) {
+    for entry in candidates.entries {
+        store.set_if_not_exists(entry.ordinal, watchlist_key(&entry.address), &entry.reason);
+    }
+}
+
+pub(crate) fn watchlist_key(address: &str) -> String {
+    format!("wallet:{}", address)
+}
+
+pub(crate) fn is_watched(watchlist: &StoreGetString, address: &str) -> bool {
+    watchlist.has_last(watchlist_key(address))
+}
+
+/// USDC/USDT moved per signer in one transaction, in USD: the larger of what the signer
+/// paid and received, so a USDC -> USDT swap counts once rather than once per side.
+fn stable_flows(meta: &TransactionStatusMeta, signers: &[String]) -> Vec<(String, f64)> {
+    let mut pre: HashMap<u32, i128> = HashMap::new();
+    for balance in meta.pre_token_balances.iter().filter(|b| is_usd_stable(&b.mint)) {
+        pre.insert(balance.account_index, raw_amount(balance));
+    }
+
+    // (paid, received) per signer
+    let mut flows: HashMap<&str, (f64, f64)> = HashMap::new();
+    for balance in meta.post_token_balances.iter().filter(|b| is_usd_stable(&b.mint)) {
+        let signer = match signers.iter().find(|signer| **signer == balance.owner) {
+            Some(signer) => signer,
+            None => continue,
+        };
+        let decimals = balance.ui_token_amount.as_ref().map(|a| a.decimals).unwrap_or(0);
+        let delta = raw_amount(balance) - pre.get(&balance.account_index).copied().unwrap_or(0);
+        let usd = delta.abs() as f64 / 10f64.powi(decimals as i32);
+        let (paid, received) = flows.entry(signer.as_str()).or_default();
+        match delta < 0 {
+            true => *paid += usd,
+            false => *received += usd,
+        }
+    }
+
+    flows.into_iter().map(|(signer, (paid, received))| (signer.to_string(), paid.max(received))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{JUPITER_V6_PROGRAM_ID, TOKEN_PROGRAM_ID};
+    use substreams_solana::pb::sf::solana::r#type::v1::{
+        Block, CompiledInstruction, ConfirmedTransaction, Message, MessageHeader, TokenBalance, Transaction,
+        UiTokenAmount,
+    };
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const USDT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
+    #[test]
+    fn stable_to_stable_swap_counts_once() {
+        let signer = base58::encode([1u8; 32]);
+        let balance = |account_index: u32, mint: &str, amount: u64| TokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            owner: signer.clone(),
+            ui_token_amount: Some(UiTokenAmount {
+                ui_amount: amount as f64 / 1e6,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: String::new(),
+            }),
+            program_id: TOKEN_PROGRAM_ID.to_string(),
+        };
+        // 50 USDC swapped for 50 USDT through Jupiter.
+        let block: FilteredBlock = Block {
+            slot: 1,
+            transactions: vec![ConfirmedTransaction {
+                transaction: Some(Transaction {
+                    signatures: vec![vec![9u8; 64]],
+                    message: Some(Message {
+                        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+                        account_keys: vec![
+                            vec![1u8; 32],
+                            vec![2u8; 32],
+                            vec![3u8; 32],
+                            base58::decode(JUPITER_V6_PROGRAM_ID).unwrap(),
+                        ],
+                        instructions: vec![CompiledInstruction { program_id_index: 3, accounts: vec![0, 1, 2], data: vec![] }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: Some(TransactionStatusMeta {
+                    pre_token_balances: vec![balance(1, USDC, 100_000_000), balance(2, USDT, 0)],
+                    post_token_balances: vec![balance(1, USDC, 50_000_000), balance(2, USDT, 50_000_000)],
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        }
+        .into();
+
+        let reasons = |params: &str| -> Vec<String> {
+            let entries = __impl_map_watchlist_candidates(params.to_string(), block.clone()).unwrap().entries;
+            entries.into_iter().map(|entry| entry.reason).collect()
+        };
+        assert_eq!(reasons("jupiter_usd=50"), vec!["jupiter_usd:50".to_string()]);
+        assert!(reasons("jupiter_usd=51").is_empty());
+    }
+}
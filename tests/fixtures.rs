@@ -0,0 +1,284 @@
+//! Runs every map and sink handler natively over every `tests/fixtures/*.binpb` file, each a
+//! serialized `sf.solana.type.v1.Block`, and compares their outputs with the golden
+//! `tests/fixtures/<name>.json` next to it. Record ids must also be unique within each
+//! module's output.
+//!
+//! Blocks converted by `rpc_to_block` keep their RPC response in `tests/fixtures/rpc/`.
+//!
+//! `UPDATE_GOLDEN=1 cargo test --test fixtures` rewrites the golden files; review the
+//! diff before committing them.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use jupiter_dex_substreams::pb::sf::jupiter::v1::{BalanceChanges, PriceUpdates, WalletBalance};
+use jupiter_dex_substreams::pb::sf::substreams::sink::entity::v1::{value::Typed, EntityChanges, Value as EntityValue};
+use jupiter_dex_substreams::pb::sf::substreams::sink::kv::v1::KvOperations;
+use jupiter_dex_substreams::*;
+use prost::Message;
+use serde_json::{json, Map, Value};
+use substreams::pb::substreams::store_delta::Operation;
+use substreams::scalar::BigInt;
+use substreams::store::{DeltaBigInt, Deltas, StoreGet, StoreGetString};
+use substreams_database_change::pb::database::{table_change::PrimaryKey, DatabaseChanges};
+use substreams_solana::pb::sf::solana::r#type::v1::Block;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Outputs of every handler reading the block, down to the sinks, keyed by module name,
+/// with the params `substreams.yaml` ships (empty unless noted).
+///
+/// Stores keep no state natively, so inputs read from a store are empty: prices, daily
+/// totals and order totals. `kv_out` gets the owner balance deltas that stores starting
+/// empty at this block would emit.
+fn run_handlers(block: Block) -> Value {
+    let keys = block_index::__impl_index_programs(block.clone()).unwrap();
+    let filtered = filtered_block::__impl_map_filtered_block(String::new(), block.clone()).unwrap();
+    // Positions of the transactions the first stage kept.
+    let kept: Vec<u32> = filtered.indexed_transactions().map(|(tx_index, _)| tx_index).collect();
+
+    // The watchlist store is only read with `watchlist=true`, which the fixtures do not use.
+    let watchlist = StoreGetString::new(0);
+    let changes = balances::__impl_map_balance_changes(String::new(), filtered.clone(), watchlist).unwrap();
+    let transactions = transactions::__impl_map_transactions(String::new(), filtered.clone()).unwrap();
+    let trades = trades::__impl_map_trades(changes.clone(), filtered.clone()).unwrap();
+    let routes = swap_routes::__impl_map_swap_routes(String::new(), filtered.clone()).unwrap();
+    let orders = order_events::__impl_map_order_events(filtered.clone()).unwrap();
+
+    let owners = spl_account_store::__impl_map_spl_initialized_account(block.clone()).unwrap();
+    let trading = jupiter_trading_store::__impl_map_jupiter_trading_data(block.clone()).unwrap();
+    let prices = token_price_store::__impl_map_token_prices(trading.clone()).unwrap();
+    let instructions =
+        jupiter_instructions::__impl_map_jupiter_instructions(block, owners.clone(), trading.clone(), prices.clone())
+            .unwrap();
+
+    let database = db_out::__impl_db_out(
+        String::new(),
+        changes.clone(),
+        transactions.clone(),
+        trades.clone(),
+        routes.clone(),
+        orders.clone(),
+        PriceUpdates::default(),
+        Deltas { deltas: Vec::new() },
+    )
+    .unwrap();
+    let entities = graph_out::__impl_graph_out(
+        changes.clone(),
+        trades.clone(),
+        routes.clone(),
+        orders.clone(),
+        Deltas { deltas: Vec::new() },
+    )
+    .unwrap();
+    let kv = wallet_balances::__impl_kv_out(changes.clone(), new_owner_balances(&changes)).unwrap();
+    let files = files_out::__impl_files_out("format=jsonl".to_string(), changes.clone(), trades.clone()).unwrap();
+
+    json!({
+        "index_programs": keys.keys,
+        "map_filtered_block": kept,
+        "map_watchlist_candidates":
+            watchlist::__impl_map_watchlist_candidates("jupiter_usd=0".to_string(), filtered.clone()).unwrap(),
+        "map_balance_changes": changes,
+        "map_transaction_classifications":
+            classification::__impl_map_transaction_classifications(String::new(), filtered).unwrap(),
+        "map_trades": trades,
+        "map_transactions": transactions,
+        "map_swap_routes": routes,
+        "map_order_events": orders,
+        "map_spl_initialized_account": owners,
+        "map_jupiter_trading_data": trading,
+        "map_token_prices": prices,
+        "map_jupiter_analytics": jupiter_analytics::__impl_map_jupiter_analytics(instructions.clone()).unwrap(),
+        "map_jupiter_instructions": instructions,
+        "db_out": database_rows(database),
+        "graph_out": entity_rows(entities),
+        "kv_out": kv_rows(kv),
+        "files_out": files.lines,
+    })
+}
+
+/// Owner totals a fresh `store_owner_balances` emits: the sum of the last balance of
+/// each of the owner's accounts in the block.
+fn new_owner_balances(changes: &BalanceChanges) -> Deltas<DeltaBigInt> {
+    let mut accounts = HashMap::new();
+    for change in changes.params.iter().filter(|change| !change.token_account.is_empty()) {
+        accounts.insert((&change.owner, &change.mint, &change.token_account, change.native), &change.raw_new_balance);
+    }
+    let mut owners: Vec<(String, BigInt)> = Vec::new();
+    for ((owner, mint, ..), balance) in accounts {
+        let key = format!("{}:{}", owner, mint);
+        let balance: BigInt = balance.parse().unwrap();
+        match owners.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, total)) => *total = total.clone() + balance,
+            None => owners.push((key, balance)),
+        }
+    }
+    owners.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Deltas {
+        deltas: owners
+            .into_iter()
+            .enumerate()
+            .map(|(ordinal, (key, total))| DeltaBigInt {
+                operation: Operation::Create,
+                ordinal: ordinal as u64,
+                key,
+                old_value: BigInt::zero(),
+                new_value: total,
+            })
+            .collect(),
+    }
+}
+
+/// Table changes sorted by table and primary key; `Tables` does not keep row order.
+fn database_rows(changes: DatabaseChanges) -> Value {
+    let mut rows: Vec<(String, String, Value)> = changes
+        .table_changes
+        .into_iter()
+        .map(|change| {
+            let pk = match &change.primary_key {
+                Some(PrimaryKey::Pk(pk)) => pk.clone(),
+                Some(PrimaryKey::CompositePk(composite)) => {
+                    let mut keys: Vec<_> = composite.keys.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    keys.sort();
+                    keys.join(",")
+                }
+                None => String::new(),
+            };
+            let fields: Map<String, Value> =
+                change.fields.iter().map(|field| (field.name.clone(), json!(field.new_value))).collect();
+            let row = json!({
+                "table": change.table,
+                "pk": pk,
+                "operation": change.operation().as_str_name(),
+                "fields": fields,
+            });
+            (change.table, pk, row)
+        })
+        .collect();
+    rows.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    Value::Array(rows.into_iter().map(|(_, _, row)| row).collect())
+}
+
+fn entity_rows(changes: EntityChanges) -> Value {
+    fn field_value(value: &EntityValue) -> Value {
+        match &value.typed {
+            Some(Typed::Int32(n)) => json!(n),
+            Some(Typed::Bigdecimal(s)) | Some(Typed::Bigint(s)) | Some(Typed::String(s)) => json!(s),
+            Some(Typed::Bytes(bytes)) => json!(hex::encode(bytes)),
+            Some(Typed::Bool(b)) => json!(b),
+            Some(Typed::Timestamp(t)) => json!(t),
+            Some(Typed::Array(array)) => Value::Array(array.value.iter().map(field_value).collect()),
+            None => Value::Null,
+        }
+    }
+
+    changes
+        .entity_changes
+        .iter()
+        .map(|change| {
+            let fields: Map<String, Value> = change
+                .fields
+                .iter()
+                .map(|field| (field.name.clone(), field.new_value.as_ref().map(field_value).unwrap_or(Value::Null)))
+                .collect();
+            json!({
+                "entity": change.entity,
+                "id": change.id,
+                "operation": change.operation().as_str_name(),
+                "fields": fields,
+            })
+        })
+        .collect()
+}
+
+fn kv_rows(operations: KvOperations) -> Value {
+    operations
+        .operations
+        .iter()
+        .map(|operation| {
+            json!({
+                "key": operation.key,
+                "type": operation.r#type().as_str_name(),
+                "value": WalletBalance::decode(operation.value.as_slice()).unwrap(),
+            })
+        })
+        .collect()
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(FIXTURES)
+        .expect("tests/fixtures exists")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "binpb"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Records of one module sharing an `id`, which sinks use as the primary key.
+fn duplicate_ids(output: &Value) -> Vec<String> {
+    let mut seen = HashSet::new();
+    output
+        .as_object()
+        .into_iter()
+        .flat_map(|fields| fields.values())
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(|record| record.get("id").and_then(Value::as_str))
+        .filter(|id| !seen.insert(id.to_string()))
+        .map(str::to_string)
+        .collect()
+}
+
+fn check(fixture: &Path) -> Result<(), String> {
+    let block = Block::decode(fs::read(fixture).unwrap().as_slice())
+        .map_err(|err| format!("{}: not a sf.solana.type.v1.Block: {}", fixture.display(), err))?;
+    let outputs = run_handlers(block);
+    for (module, output) in outputs.as_object().unwrap() {
+        let duplicates = duplicate_ids(output);
+        if !duplicates.is_empty() {
+            return Err(format!("{}: {} repeats ids {:?}", fixture.display(), module, duplicates));
+        }
+    }
+    let actual = serde_json::to_string_pretty(&outputs).unwrap() + "\n";
+    let golden = fixture.with_extension("json");
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, actual).unwrap();
+        return Ok(());
+    }
+    let expected = fs::read_to_string(&golden)
+        .map_err(|_| format!("{}: no golden file, run with UPDATE_GOLDEN=1", golden.display()))?;
+    if actual == expected {
+        return Ok(());
+    }
+
+    // Name the first module whose output differs, then show both versions of it.
+    let actual: Value = serde_json::from_str(&actual).unwrap();
+    let expected: Value = serde_json::from_str(&expected).unwrap();
+    let modules = actual.as_object().unwrap();
+    let (module, output) = modules
+        .iter()
+        .find(|(module, output)| expected.get(module.as_str()) != Some(*output))
+        .unwrap_or_else(|| modules.iter().next().unwrap());
+    Err(format!(
+        "{}: {} differs from {}\nexpected: {}\nactual:   {}",
+        fixture.display(),
+        module,
+        golden.display(),
+        expected.get(module.as_str()).unwrap_or(&Value::Null),
+        output
+    ))
+}
+
+#[test]
+fn handlers_match_golden_outputs() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no .binpb fixtures in {}", FIXTURES);
+
+    let failures: Vec<String> = fixtures.iter().filter_map(|fixture| check(fixture).err()).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
@@ -0,0 +1,88 @@
+//! Runs the `rpc_to_block` binary over the RPC responses kept in `tests/fixtures/rpc/`, one
+//! `json` and one `jsonParsed` block, and checks it reproduces the checked-in `.binpb`
+//! fixtures. Needs `--features bin`.
+
+use std::fs;
+use std::process::Command;
+
+use prost::Message;
+use substreams_solana::pb::sf::solana::r#type::v1::Block;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Converts `rpc/<name>.json` and returns it with the checked-in `<name>.binpb`.
+fn convert(name: &str, slot: u64) -> (Block, Block) {
+    let output = format!("{}/{}.binpb", env!("CARGO_TARGET_TMPDIR"), name);
+    let status = Command::new(env!("CARGO_BIN_EXE_rpc_to_block"))
+        .args(["--slot", &slot.to_string(), &output])
+        .arg(format!("{}/rpc/{}.json", FIXTURES, name))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let actual = Block::decode(fs::read(&output).unwrap().as_slice()).unwrap();
+    let expected = fs::read(format!("{}/{}.binpb", FIXTURES, name)).unwrap();
+    (actual, Block::decode(expected.as_slice()).unwrap())
+}
+
+#[test]
+fn converts_rpc_block_to_fixture() {
+    let (actual, expected) = convert("jupiter_route_and_limit_fill", 380000123);
+    assert_eq!(actual.slot, 380000123);
+    assert_eq!(actual.transactions.len(), 3);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn converts_json_parsed_block_to_fixture() {
+    let (actual, expected) = convert("jupiter_parsed_route_and_limit_open", 380000533);
+    assert_eq!(actual.transactions.len(), 2);
+    // Parsed instructions come back in binary form: the associated token account creation,
+    // the SOL wrap and the memo of the route.
+    let route = actual.transactions[0].transaction.as_ref().unwrap().message.as_ref().unwrap();
+    assert_eq!(route.instructions[1].data, vec![1]);
+    assert_eq!(route.instructions[1].accounts.len(), 6);
+    assert_eq!(route.instructions[3].data, vec![17]);
+    assert_eq!(route.instructions[7].data, b"jupiter jsonParsed fixture".to_vec());
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn fails_on_unsupported_parsed_instructions() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let input = format!("{}/parsed_vote.json", dir);
+    let vote = "Vote111111111111111111111111111111111111111";
+    let payer = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+    let transaction = serde_json::json!({
+        "slot": 1,
+        "blockTime": null,
+        "transaction": {
+            "signatures": ["1111111111111111111111111111111111111111111111111111111111111111"],
+            "message": {
+                "accountKeys": [
+                    { "pubkey": payer, "signer": true, "writable": true, "source": "transaction" },
+                    { "pubkey": vote, "signer": false, "writable": false, "source": "transaction" }
+                ],
+                "recentBlockhash": "11111111111111111111111111111111",
+                "instructions": [
+                    {
+                        "program": "vote",
+                        "programId": vote,
+                        "parsed": { "type": "towersync", "info": {} },
+                        "stackHeight": null
+                    }
+                ]
+            }
+        },
+        "meta": { "err": null, "fee": 5000, "preBalances": [1, 1], "postBalances": [1, 1] }
+    });
+    fs::write(&input, transaction.to_string()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rpc_to_block"))
+        .args([format!("{}/parsed_vote.binpb", dir), input])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("json or base64"), "{}", stderr);
+}